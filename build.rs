@@ -0,0 +1,31 @@
+// 生成已嵌入的 migration 版本列表，供 /_ready 检查数据库是否已迁移
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let dir = Path::new("migrations/postgres");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut versions: Vec<String> = fs::read_dir(dir)
+        .expect("migrations/postgres not found")
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            if !entry.file_type().ok()?.is_dir() {
+                return None;
+            }
+            let name = entry.file_name().into_string().ok()?;
+            // 与 diesel 的 version_from_path 一致: 取第一个 '_' 之前的部分，去掉 '-'
+            let version = name.split('_').next()?.replace('-', "");
+            (!version.is_empty()).then_some(version)
+        })
+        .collect();
+    versions.sort();
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("migration_versions.rs");
+    fs::write(
+        out,
+        format!("pub const MIGRATION_VERSIONS: &[&str] = &{:?};\n", versions),
+    )
+    .unwrap();
+}
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE polls (
+  id SERIAL PRIMARY KEY,
+  post_id INTEGER NOT NULL UNIQUE REFERENCES posts(id),
+  create_time TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE poll_options (
+  id SERIAL PRIMARY KEY,
+  poll_id INTEGER NOT NULL REFERENCES polls(id),
+  idx INTEGER NOT NULL,
+  content VARCHAR NOT NULL,
+  UNIQUE (poll_id, idx)
+);
+
+CREATE TABLE poll_votes (
+  poll_id INTEGER NOT NULL REFERENCES polls(id),
+  voter_hash VARCHAR NOT NULL,
+  option_id INTEGER NOT NULL REFERENCES poll_options(id),
+  create_time TIMESTAMPTZ NOT NULL DEFAULT now(),
+  PRIMARY KEY (poll_id, voter_hash)
+);
+CREATE INDEX poll_votes_option_id_idx ON poll_votes (option_id);
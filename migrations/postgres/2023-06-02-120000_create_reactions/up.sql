@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE reactions (
+  post_id INTEGER NOT NULL REFERENCES posts(id),
+  comment_id INTEGER NOT NULL DEFAULT 0, -- 0 for the post itself
+  user_hash VARCHAR NOT NULL,
+  emoji VARCHAR NOT NULL,
+  create_time TIMESTAMPTZ NOT NULL DEFAULT now(),
+  PRIMARY KEY (post_id, comment_id, user_hash, emoji)
+);
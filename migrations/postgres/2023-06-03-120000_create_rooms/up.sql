@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE rooms (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  description VARCHAR NOT NULL DEFAULT '',
+  is_visible BOOLEAN NOT NULL DEFAULT TRUE,
+  admin_only BOOLEAN NOT NULL DEFAULT FALSE,
+  allow_delete BOOLEAN NOT NULL DEFAULT TRUE,
+  allow_tmp BOOLEAN NOT NULL DEFAULT FALSE,
+  accept_reports BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+INSERT INTO rooms (id, name, allow_tmp) VALUES (0, '树洞', TRUE);
+INSERT INTO rooms (id, name) VALUES (1, '房间1'), (2, '房间2'), (3, '房间3'), (4, '房间4');
+INSERT INTO rooms (id, name, allow_delete, accept_reports) VALUES (42, '举报', FALSE, TRUE);
+-- 之前没有校验room_id，已有的其他房间号设为不可见
+INSERT INTO rooms (id, name, is_visible)
+SELECT DISTINCT room_id, '房间' || room_id, FALSE FROM posts
+ON CONFLICT (id) DO NOTHING;
+SELECT setval('rooms_id_seq', (SELECT MAX(id) FROM rooms));
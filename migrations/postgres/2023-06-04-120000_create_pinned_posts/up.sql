@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE pinned_posts (
+  post_id INTEGER PRIMARY KEY REFERENCES posts(id),
+  room_id INTEGER, -- NULL for the list of all rooms
+  pinned_by VARCHAR NOT NULL,
+  create_time TIMESTAMPTZ NOT NULL DEFAULT now(),
+  expire_time TIMESTAMPTZ
+);
+CREATE INDEX pinned_posts_room_id_idx ON pinned_posts (room_id);
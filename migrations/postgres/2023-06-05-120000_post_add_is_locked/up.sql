@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE posts
+ADD COLUMN is_locked BOOLEAN NOT NULL DEFAULT FALSE
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX posts_expire_time_idx;
+ALTER TABLE posts
+DROP COLUMN expire_time;
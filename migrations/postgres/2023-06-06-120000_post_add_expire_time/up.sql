@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE posts
+ADD COLUMN expire_time TIMESTAMPTZ;
+CREATE INDEX posts_expire_time_idx ON posts (expire_time) WHERE expire_time IS NOT NULL;
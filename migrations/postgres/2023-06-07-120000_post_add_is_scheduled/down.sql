@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX posts_scheduled_idx;
+DROP INDEX posts_create_time_idx;
+ALTER TABLE posts
+DROP COLUMN is_scheduled;
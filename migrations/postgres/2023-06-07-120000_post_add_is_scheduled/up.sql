@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE posts
+ADD COLUMN is_scheduled BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX posts_scheduled_idx ON posts (create_time) WHERE is_scheduled;
+CREATE INDEX posts_create_time_idx ON posts (create_time);
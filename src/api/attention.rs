@@ -8,17 +8,20 @@ use rocket::form::Form;
 use rocket::serde::json::json;
 use rocket::serde::json::serde_json;
 use rocket::serde::Serialize;
-use std::fs::File;
+use rocket::State;
+use std::fs;
 use url::Url;
 use web_push::{
     ContentEncoding, SubscriptionInfo, VapidSignatureBuilder, WebPushClient, WebPushMessageBuilder,
 };
 
-#[derive(FromForm)]
-pub struct AttentionInput {
-    pid: i32,
-    #[field(validate = range(0..2))]
-    switch: i32,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct AttentionInput {
+        pid: i32,
+        #[field(validate = range(0..2))]
+        switch: i32,
+    }
 }
 
 #[post("/attention", data = "<ai>")]
@@ -88,12 +91,32 @@ pub async fn get_attention(user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi
     code0!(ps_data)
 }
 
-#[derive(FromForm)]
-pub struct NotificatinInput {
-    enable: bool,
-    endpoint: String,
-    auth: String,
-    p256dh: String,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct NotificatinInput {
+        enable: bool,
+        endpoint: String,
+        auth: String,
+        p256dh: String,
+    }
+}
+
+// web push 的私钥，启动时读取，读取失败时不发送推送
+pub struct PushKey(Option<Vec<u8>>);
+
+impl PushKey {
+    pub fn load(path: &str) -> Self {
+        match fs::read(path) {
+            Ok(key) => Self(Some(key)),
+            Err(e) => {
+                warn!(
+                    "read push_private_key {} failed, web push disabled: {}",
+                    path, e
+                );
+                Self(None)
+            }
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -105,7 +128,12 @@ struct PushData {
 }
 
 #[post("/post/<pid>/notification", data = "<ni>")]
-pub async fn set_notification(pid: i32, ni: Form<NotificatinInput>, _user: CurrentUser) -> JsonApi {
+pub async fn set_notification(
+    pid: i32,
+    ni: Form<NotificatinInput>,
+    _user: CurrentUser,
+    push_key: &State<PushKey>,
+) -> JsonApi {
     let url_host = Url::parse(&ni.endpoint)
         .map_err(|_| UnknownPushEndpoint)?
         .host()
@@ -115,11 +143,10 @@ pub async fn set_notification(pid: i32, ni: Form<NotificatinInput>, _user: Curre
         .then_some(())
         .ok_or(UnknownPushEndpoint)?;
 
-    if ni.enable {
+    if let Some(key) = push_key.0.as_deref().filter(|_| ni.enable) {
         let subscription_info = SubscriptionInfo::new(&ni.endpoint, &ni.p256dh, &ni.auth);
 
-        let file = File::open("keys/private.pem").unwrap();
-        let sig_builder = VapidSignatureBuilder::from_pem(file, &subscription_info)
+        let sig_builder = VapidSignatureBuilder::from_pem(key, &subscription_info)
             .unwrap()
             .build()
             .unwrap();
@@ -0,0 +1,32 @@
+use crate::api::{CurrentUser, JsonApi, PolicyError::*};
+use crate::cache::{cache_metrics, flush_cache_family, inspect_post_cache, CACHE_FAMILIES};
+use crate::rds_conn::RdsConn;
+use rocket::serde::json::json;
+
+#[get("/admin/cache/metrics")]
+pub async fn get_cache_metrics(user: CurrentUser) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    code0!(json!({
+        "caches": cache_metrics(),
+        "families": CACHE_FAMILIES,
+    }))
+}
+
+#[get("/admin/cache/post/<pid>")]
+pub async fn inspect_post(pid: i32, user: CurrentUser, rconn: RdsConn) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    code0!(inspect_post_cache(&rconn, pid).await?)
+}
+
+#[post("/admin/cache/<family>/flush")]
+pub async fn flush_cache(family: &str, user: CurrentUser, rconn: RdsConn) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    let n = flush_cache_family(&rconn, family)
+        .await
+        .ok_or(UnknownCacheFamily)?;
+
+    code0!(json!({ "deleted": n }))
+}
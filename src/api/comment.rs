@@ -1,3 +1,4 @@
+use crate::api::reaction::{summarize_emoji, EmojiCount};
 use crate::api::{ApiError, CurrentUser, JsonApi, PolicyError::*, Ugc};
 use crate::cache::BlockDictCache;
 use crate::db_conn::Db;
@@ -9,28 +10,38 @@ use rocket::form::Form;
 use rocket::serde::{json::json, Serialize};
 use std::collections::HashMap;
 
-#[derive(FromForm)]
-pub struct CommentInput {
-    #[field(validate = len(1..12289))]
-    text: String,
-    use_title: Option<i8>,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct CommentInput {
+        #[field(validate = len(1..12289))]
+        text: String,
+        use_title: Option<i8>,
+    }
 }
 
-#[derive(Serialize)]
-#[serde(crate = "rocket::serde")]
-pub struct CommentOutput {
-    cid: i32,
-    text: String,
-    author_title: String,
-    can_del: bool,
-    name_id: i32,
-    is_tmp: bool,
-    create_time: i64,
-    is_blocked: bool,
-    //blocked_count: Option<i32>,
-    // for old version frontend
-    timestamp: i64,
-    blocked: bool,
+api_schema! {
+    #[derive(Serialize)]
+    #[serde(crate = "rocket::serde")]
+    pub struct CommentOutput {
+        cid: i32,
+        text: String,
+        author_title: String,
+        can_del: bool,
+        /// 同一条洞中的发言者编号，0 为洞主
+        name_id: i32,
+        is_tmp: bool,
+        create_time: i64,
+        is_blocked: bool,
+        //blocked_count: Option<i32>,
+        reactions: Vec<EmojiCount>,
+        my_reactions: Vec<String>,
+        // for old version frontend
+        /// 同 create_time
+        timestamp: i64,
+        /// 同 is_blocked
+        blocked: bool,
+    }
+    deprecated(timestamp, blocked)
 }
 
 pub async fn c2output<'r>(
@@ -38,7 +49,9 @@ pub async fn c2output<'r>(
     cs: &[Comment],
     user: &CurrentUser,
     cached_block_dict: &HashMap<String, bool>,
-    //rconn: &RdsConn,
+    emoji_reactions: &[EmojiReaction],
+    db: &Db,
+    rconn: &RdsConn,
 ) -> Vec<CommentOutput> {
     let mut hash2id = HashMap::<&String, i32>::from([(&p.author_hash, 0)]);
     let name_ids_iter = cs.iter().map(|c| match hash2id.get(&c.author_hash) {
@@ -56,11 +69,12 @@ pub async fn c2output<'r>(
             let is_blocked = cached_block_dict[&c.author_hash];
             let can_view = user.is_admin
                 || (!is_blocked && user.id.is_some() || user.namehash.eq(&c.author_hash));
+            let (reactions, my_reactions) = summarize_emoji(emoji_reactions, c.id, &user.namehash);
             Some(CommentOutput {
                 cid: c.id,
                 text: (if can_view { &c.content } else { "" }).to_string(),
                 author_title: c.author_title.to_string(),
-                can_del: c.check_permission(user, "wd").is_ok(),
+                can_del: c.check_delete_permission(user, db, rconn).await.is_ok(),
                 name_id,
                 is_tmp: c.is_tmp,
                 create_time: c.create_time.timestamp(),
@@ -75,6 +89,8 @@ pub async fn c2output<'r>(
                     None
                 },
                 */
+                reactions,
+                my_reactions,
                 timestamp: c.create_time.timestamp(),
                 blocked: is_blocked,
             })
@@ -92,12 +108,25 @@ pub async fn get_comment(pid: i32, user: CurrentUser, db: Db, rconn: RdsConn) ->
     if p.is_deleted {
         return Err(ApiError::Pc(IsDeleted));
     }
+    if p.is_scheduled {
+        return Err(ApiError::Pc(IsScheduled));
+    }
     let cs = p.get_comments(&db, &rconn).await?;
     let hash_list = cs.iter().map(|c| &c.author_hash).collect::<Vec<_>>();
     let cached_block_dict = BlockDictCache::init(&user.namehash, p.id, &rconn)
         .get_or_create(&user, &hash_list)
         .await?;
-    let data = c2output(&p, &cs, &user, &cached_block_dict).await;
+    let emoji_reactions = EmojiReaction::gets_by_post_id(&db, &rconn, p.id).await?;
+    let data = c2output(
+        &p,
+        &cs,
+        &user,
+        &cached_block_dict,
+        &emoji_reactions,
+        &db,
+        &rconn,
+    )
+    .await;
 
     Ok(json!({
         "code": 0,
@@ -121,6 +150,12 @@ pub async fn add_comment(
     if p.author_hash != user.namehash {
         user.id.ok_or(YouAreTmp)?;
     }
+    if p.is_scheduled {
+        Err(IsScheduled)?;
+    }
+    if p.is_locked && !user.is_admin {
+        Err(IsLocked)?;
+    }
     let use_title = ci.use_title.is_some() || user.is_admin || user.is_candidate;
     let c = Comment::create(
         &db,
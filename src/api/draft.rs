@@ -0,0 +1,107 @@
+use crate::api::{Api, CurrentUser, JsonApi, PolicyError::*};
+use crate::rds_conn::RdsConn;
+use crate::rds_models::*;
+use chrono::offset::Local;
+use rocket::form::Form;
+use rocket::serde::json::{json, Value};
+
+const MAX_POST_DRAFTS: usize = 10;
+const MAX_COMMENT_DRAFTS: usize = 50;
+
+// 洞的草稿按名字区分，评论的草稿按pid区分
+fn draft_target(name: &Option<String>, pid: Option<i32>) -> Api<(&'static str, String, usize)> {
+    match (name, pid) {
+        (Some(name), None) if !name.is_empty() => Ok(("post", name.clone(), MAX_POST_DRAFTS)),
+        (None, Some(pid)) => Ok(("comment", pid.to_string(), MAX_COMMENT_DRAFTS)),
+        _ => Err(NotAllowed)?,
+    }
+}
+
+fn draft2json(kind: &str, field: &str, d: &DraftData, with_text: bool) -> Value {
+    json!({
+        "name": (kind == "post").then(|| field),
+        "pid": (kind == "comment").then(|| field.parse::<i32>().ok()).flatten(),
+        "cw": d.cw,
+        "text": with_text.then(|| &d.text),
+        "length": d.text.chars().count(),
+        "update_time": d.update_time.timestamp(),
+    })
+}
+
+api_schema! {
+    #[derive(FromForm)]
+    pub struct DraftInput {
+        #[field(validate = len(0..33))]
+        name: Option<String>,
+        pid: Option<i32>,
+        #[field(validate = len(0..12289))]
+        text: String,
+        #[field(validate = len(0..97))]
+        cw: String,
+    }
+}
+
+#[post("/draft", data = "<di>")]
+pub async fn save_draft(di: Form<DraftInput>, user: CurrentUser, rconn: RdsConn) -> JsonApi {
+    let (kind, field, max_n) = draft_target(&di.name, di.pid)?;
+    let di = di.into_inner();
+    let data = DraftData {
+        text: di.text,
+        cw: di.cw,
+        update_time: Local::now(),
+    };
+
+    if !Draft::init(kind, &user.namehash, &rconn)
+        .set(&field, &data, max_n)
+        .await?
+    {
+        Err(TooManyDrafts)?;
+    }
+
+    code0!()
+}
+
+#[get("/drafts")]
+pub async fn list_drafts(user: CurrentUser, rconn: RdsConn) -> JsonApi {
+    let mut drafts: Vec<Value> = vec![];
+    for kind in ["post", "comment"] {
+        let mut ds = Draft::init(kind, &user.namehash, &rconn).all().await?;
+        ds.sort_by(|(_, a), (_, b)| b.update_time.cmp(&a.update_time));
+        drafts.extend(ds.iter().map(|(f, d)| draft2json(kind, f, d, false)));
+    }
+
+    code0!(drafts)
+}
+
+#[get("/draft?<name>&<pid>")]
+pub async fn load_draft(
+    name: Option<String>,
+    pid: Option<i32>,
+    user: CurrentUser,
+    rconn: RdsConn,
+) -> JsonApi {
+    let (kind, field, _) = draft_target(&name, pid)?;
+    let d = Draft::init(kind, &user.namehash, &rconn)
+        .get(&field)
+        .await?;
+
+    code0!(d.map(|d| draft2json(kind, &field, &d, true)))
+}
+
+api_schema! {
+    #[derive(FromForm)]
+    pub struct DraftTarget {
+        name: Option<String>,
+        pid: Option<i32>,
+    }
+}
+
+#[post("/draft/delete", data = "<dt>")]
+pub async fn delete_draft(dt: Form<DraftTarget>, user: CurrentUser, rconn: RdsConn) -> JsonApi {
+    let (kind, field, _) = draft_target(&dt.name, dt.pid)?;
+    Draft::init(kind, &user.namehash, &rconn)
+        .rem(&field)
+        .await?;
+
+    code0!()
+}
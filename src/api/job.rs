@@ -0,0 +1,20 @@
+use crate::api::{CurrentUser, JsonApi, PolicyError::*};
+use crate::jobs::Jobs;
+use rocket::serde::json::json;
+use rocket::State;
+
+#[get("/admin/jobs")]
+pub async fn get_jobs(user: CurrentUser, jobs: &State<Jobs>) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    code0!(jobs.status())
+}
+
+#[post("/admin/jobs/<name>/run")]
+pub async fn run_job(name: &str, user: CurrentUser, jobs: &State<Jobs>) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    jobs.trigger(name).then_some(()).ok_or(UnknownJob)?;
+
+    code0!()
+}
@@ -1,6 +1,8 @@
 #![allow(clippy::unnecessary_lazy_evaluations)]
 
 use crate::db_conn::Db;
+use crate::i18n::{Lang, Msg, UserLang};
+use crate::metrics::API_ERRORS;
 use crate::models::*;
 use crate::random_hasher::RandomHasher;
 use crate::rds_conn::RdsConn;
@@ -35,26 +37,38 @@ macro_rules! code1 {
 }
 */
 
-macro_rules! e2s {
-    ($e:expr) => (json!({
+// 失败时的返回值: code 固定为 -1 以兼容旧版前端，err 为稳定的错误标识，msg 仅用于展示
+fn error_body(err: &str, msg: &str) -> Value {
+    json!({
         "code": -1,
-        "msg": $e.to_string()
-    }));
+        "err": err,
+        "msg": msg,
+    })
 }
 
 #[catch(401)]
-pub fn catch_401_error() -> &'static str {
-    "未登录或token过期"
+pub fn catch_401_error(req: &Request) -> Value {
+    error_body("unauthorized", Msg::NotLoggedIn.text(Lang::of(req)))
 }
 
 #[catch(403)]
-pub fn catch_403_error() -> &'static str {
-    "可能被封禁了，等下次重置吧"
+pub fn catch_403_error(req: &Request) -> Value {
+    error_body("banned", Msg::Banned.text(Lang::of(req)))
+}
+
+#[catch(503)]
+pub fn catch_503_error(req: &Request) -> Value {
+    error_body("unavailable", Msg::Unavailable.text(Lang::of(req)))
 }
 
 #[catch(404)]
-pub fn catch_404_error() -> &'static str {
-    "请更新前端版本"
+pub fn catch_404_error(req: &Request) -> Value {
+    error_body("unknown_api", Msg::UpdateFrontend.text(Lang::of(req)))
+}
+
+#[catch(422)]
+pub fn catch_422_error(req: &Request) -> Value {
+    error_body("invalid_form", Msg::InvalidForm.text(Lang::of(req)))
 }
 
 pub struct CurrentUser {
@@ -65,10 +79,12 @@ pub struct CurrentUser {
     custom_title: Option<String>,
     title_secret: Option<String>,
     pub auto_block_rank: u8,
+    pub lang: Lang,
 }
 
 impl CurrentUser {
-    pub async fn from_hash(rconn: &RdsConn, namehash: String) -> Self {
+    // 未设置语言时使用 fallback
+    pub async fn from_hash(rconn: &RdsConn, namehash: String, fallback: Lang) -> Self {
         let (custom_title, title_secret) = CustomTitle::get(rconn, &namehash)
             .await
             .ok()
@@ -80,6 +96,11 @@ impl CurrentUser {
             custom_title,
             title_secret,
             auto_block_rank: AutoBlockRank::get(rconn, &namehash).await.unwrap_or(2),
+            lang: LangSetting::get(rconn, &namehash)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(fallback),
             namehash,
         }
     }
@@ -91,26 +112,28 @@ impl<'r> FromRequest<'r> for CurrentUser {
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         let rh = request.rocket().state::<RandomHasher>().unwrap();
         let rconn = try_outcome!(request.guard::<RdsConn>().await);
+        let fallback = Lang::from_headers(request);
 
         if let Some(user) = {
             if let Some(token) = request.headers().get_one("User-Token") {
                 let sp = token.split('_').collect::<Vec<&str>>();
                 if sp.len() == 2 && sp[0] == rh.get_tmp_token() {
-                    Some(CurrentUser::from_hash(&rconn, rh.hash_with_salt(sp[1])).await)
+                    Some(CurrentUser::from_hash(&rconn, rh.hash_with_salt(sp[1]), fallback).await)
                 } else {
                     let db = try_outcome!(request.guard::<Db>().await);
                     if let Some(u) = User::get_by_token(&db, &rconn, token).await {
                         let namehash = rh.hash_with_salt(&u.name);
-                        let user_base = CurrentUser::from_hash(&rconn, namehash).await;
+                        let user_base = CurrentUser::from_hash(&rconn, namehash, fallback).await;
+                        // redis 不可用时按非选举管理员处理
                         Some(CurrentUser {
                             id: Some(u.id),
                             is_admin: u.is_admin
                                 || is_elected_admin(&rconn, &user_base.custom_title)
                                     .await
-                                    .unwrap(),
+                                    .unwrap_or(false),
                             is_candidate: is_elected_candidate(&rconn, &user_base.custom_title)
                                 .await
-                                .unwrap(),
+                                .unwrap_or(false),
                             ..user_base
                         })
                     } else {
@@ -121,10 +144,14 @@ impl<'r> FromRequest<'r> for CurrentUser {
                 None
             }
         } {
-            if BannedUsers::has(&rconn, &user.namehash).await.unwrap() {
-                Outcome::Failure((Status::Forbidden, ()))
-            } else {
-                Outcome::Success(user)
+            request.local_cache(|| UserLang(Some(user.lang)));
+            match BannedUsers::has(&rconn, &user.namehash).await {
+                Ok(true) => Outcome::Failure((Status::Forbidden, ())),
+                Ok(false) => Outcome::Success(user),
+                Err(e) => {
+                    warn!("check banned user failed: {}", e);
+                    Outcome::Failure((Status::ServiceUnavailable, ()))
+                }
             }
         } else {
             Outcome::Failure((Status::Unauthorized, ()))
@@ -143,7 +170,19 @@ pub enum PolicyError {
     InvalidTitle,
     YouAreTmp,
     NoReason,
+    InvalidFormat,
+    UnknownJob,
+    UnknownCacheFamily,
     UnknownPushEndpoint,
+    UnknownEmoji,
+    TooManyPinned,
+    IsLocked,
+    InvalidExpireTime,
+    IsScheduled,
+    InvalidPublishTime,
+    TooManyDrafts,
+    UnknownLang,
+    InvalidCursor,
 }
 
 #[derive(Debug)]
@@ -155,33 +194,115 @@ pub enum ApiError {
     IO(std::io::Error),
 }
 
-impl<'r> Responder<'r, 'static> for ApiError {
-    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+impl PolicyError {
+    pub fn err(&self) -> &'static str {
         match self {
-            ApiError::Db(e) => e2s!(e).respond_to(req),
-            ApiError::Rds(e) => e2s!(e).respond_to(req),
-            ApiError::WebPush(e) => e2s!(e).respond_to(req),
-            ApiError::IO(e) => e2s!(e).respond_to(req),
-            ApiError::Pc(e) => json!({
-                "code": -1,
-                "msg": match e {
-                    PolicyError::IsReported => "内容被举报，处理中",
-                    PolicyError::IsPrivate => "未被设置为公开",
-                    PolicyError::IsDeleted => "内容被删除",
-                    PolicyError::NotAllowed => "不允许的操作",
-                    PolicyError::TitleUsed => "头衔已被使用",
-                    PolicyError::TitleProtected => "头衔处于保护期",
-                    PolicyError::InvalidTitle => "头衔包含不允许的符号",
-                    PolicyError::YouAreTmp => "临时用户只可发布内容",
-                    PolicyError::NoReason => "未填写理由",
-                    PolicyError::UnknownPushEndpoint => "未知的浏览器推送地址",
-                }
-            })
-            .respond_to(req),
+            PolicyError::IsReported => "is_reported",
+            PolicyError::IsPrivate => "is_private",
+            PolicyError::IsDeleted => "is_deleted",
+            PolicyError::NotAllowed => "not_allowed",
+            PolicyError::TitleUsed => "title_used",
+            PolicyError::TitleProtected => "title_protected",
+            PolicyError::InvalidTitle => "invalid_title",
+            PolicyError::YouAreTmp => "you_are_tmp",
+            PolicyError::NoReason => "no_reason",
+            PolicyError::InvalidFormat => "invalid_format",
+            PolicyError::UnknownJob => "unknown_job",
+            PolicyError::UnknownCacheFamily => "unknown_cache_family",
+            PolicyError::UnknownPushEndpoint => "unknown_push_endpoint",
+            PolicyError::UnknownEmoji => "unknown_emoji",
+            PolicyError::TooManyPinned => "too_many_pinned",
+            PolicyError::IsLocked => "is_locked",
+            PolicyError::InvalidExpireTime => "invalid_expire_time",
+            PolicyError::IsScheduled => "is_scheduled",
+            PolicyError::InvalidPublishTime => "invalid_publish_time",
+            PolicyError::TooManyDrafts => "too_many_drafts",
+            PolicyError::UnknownLang => "unknown_lang",
+            PolicyError::InvalidCursor => "invalid_cursor",
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            PolicyError::IsDeleted
+            | PolicyError::IsScheduled
+            | PolicyError::UnknownJob
+            | PolicyError::UnknownCacheFamily => Status::NotFound,
+            PolicyError::IsReported
+            | PolicyError::IsPrivate
+            | PolicyError::NotAllowed
+            | PolicyError::YouAreTmp
+            | PolicyError::IsLocked => Status::Forbidden,
+            PolicyError::TitleUsed
+            | PolicyError::TitleProtected
+            | PolicyError::TooManyPinned
+            | PolicyError::TooManyDrafts => Status::Conflict,
+            PolicyError::InvalidTitle
+            | PolicyError::NoReason
+            | PolicyError::InvalidFormat
+            | PolicyError::UnknownPushEndpoint
+            | PolicyError::UnknownEmoji
+            | PolicyError::InvalidExpireTime
+            | PolicyError::InvalidPublishTime
+            | PolicyError::UnknownLang
+            | PolicyError::InvalidCursor => Status::UnprocessableEntity,
+        }
+    }
+}
+
+impl ApiError {
+    fn metric_labels(&self) -> String {
+        match self {
+            ApiError::Db(_) => "variant=\"Db\"".to_string(),
+            ApiError::Rds(_) => "variant=\"Rds\"".to_string(),
+            ApiError::WebPush(_) => "variant=\"WebPush\"".to_string(),
+            ApiError::IO(_) => "variant=\"IO\"".to_string(),
+            ApiError::Pc(e) => format!("variant=\"Pc\",policy=\"{:?}\"", e),
+        }
+    }
+
+    pub fn err(&self) -> &'static str {
+        match self {
+            ApiError::Db(diesel::result::Error::NotFound) => "not_found",
+            ApiError::Db(_) => "db_error",
+            ApiError::Rds(e) if e.kind() == redis::ErrorKind::IoError => "unavailable",
+            ApiError::Rds(_) => "rds_error",
+            ApiError::WebPush(_) => "web_push_error",
+            ApiError::IO(_) => "io_error",
+            ApiError::Pc(e) => e.err(),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::Db(diesel::result::Error::NotFound) => Status::NotFound,
+            ApiError::Rds(e) if e.kind() == redis::ErrorKind::IoError => Status::ServiceUnavailable,
+            ApiError::WebPush(_) => Status::BadGateway,
+            ApiError::Pc(e) => e.status(),
+            _ => Status::InternalServerError,
         }
     }
 }
 
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        API_ERRORS.inc(self.metric_labels());
+        let lang = Lang::of(req);
+        let msg = match &self {
+            ApiError::Rds(e) if e.kind() == redis::ErrorKind::IoError => {
+                warn!("redis unavailable: {}", e);
+                Msg::Unavailable.text(lang).to_string()
+            }
+            ApiError::Db(e) => e.to_string(),
+            ApiError::Rds(e) => e.to_string(),
+            ApiError::WebPush(e) => e.to_string(),
+            ApiError::IO(e) => e.to_string(),
+            ApiError::Pc(e) => Msg::Policy(e).text(lang).to_string(),
+        };
+        (self.status(), error_body(self.err(), &msg)).respond_to(req)
+    }
+}
+
 impl From<web_push::WebPushError> for ApiError {
     fn from(err: web_push::WebPushError) -> ApiError {
         ApiError::WebPush(err)
@@ -221,7 +342,8 @@ pub trait Ugc {
     fn get_is_deleted(&self) -> bool;
     fn get_is_reported(&self) -> bool;
     fn get_is_private(&self) -> bool;
-    fn extra_delete_condition(&self) -> bool;
+    fn get_is_scheduled(&self) -> bool;
+    async fn extra_delete_condition(&self, db: &Db, rconn: &RdsConn) -> Api<bool>;
     async fn do_set_deleted(&mut self, db: &Db) -> Api<()>;
     fn check_permission(&self, user: &CurrentUser, mode: &str) -> Api<()> {
         if mode.contains('r') && self.get_is_deleted() {
@@ -230,6 +352,10 @@ pub trait Ugc {
         if user.is_admin {
             return Ok(());
         }
+        if mode.contains('r') && self.get_is_scheduled() && self.get_author_hash() != user.namehash
+        {
+            return Err(ApiError::Pc(PolicyError::IsScheduled));
+        }
         if mode.contains('o') && self.get_is_reported() {
             return Err(ApiError::Pc(PolicyError::IsReported));
         }
@@ -239,14 +365,25 @@ pub trait Ugc {
         if mode.contains('w') && self.get_author_hash() != user.namehash {
             return Err(ApiError::Pc(PolicyError::NotAllowed));
         }
-        if mode.contains('d') && !self.extra_delete_condition() {
+        Ok(())
+    }
+
+    async fn check_delete_permission(
+        &self,
+        user: &CurrentUser,
+        db: &Db,
+        rconn: &RdsConn,
+    ) -> Api<()> {
+        self.check_permission(user, "w")?;
+        if !user.is_admin && !self.extra_delete_condition(db, rconn).await? {
             return Err(ApiError::Pc(PolicyError::NotAllowed));
         }
         Ok(())
     }
 
-    async fn soft_delete(&mut self, user: &CurrentUser, db: &Db) -> Api<()> {
-        self.check_permission(user, "rwd")?;
+    async fn soft_delete(&mut self, user: &CurrentUser, db: &Db, rconn: &RdsConn) -> Api<()> {
+        self.check_permission(user, "r")?;
+        self.check_delete_permission(user, db, rconn).await?;
 
         self.do_set_deleted(db).await?;
         Ok(())
@@ -264,11 +401,14 @@ impl Ugc for Post {
     fn get_is_private(&self) -> bool {
         !(self.allow_search || self.n_attentions > 20)
     }
+    fn get_is_scheduled(&self) -> bool {
+        self.is_scheduled
+    }
     fn get_is_deleted(&self) -> bool {
         self.is_deleted
     }
-    fn extra_delete_condition(&self) -> bool {
-        self.room_id != 42
+    async fn extra_delete_condition(&self, db: &Db, rconn: &RdsConn) -> Api<bool> {
+        Ok(Room::get(db, rconn, self.room_id).await?.allow_delete)
     }
     async fn do_set_deleted(&mut self, db: &Db) -> Api<()> {
         update!(*self, posts, db, { is_deleted, to true });
@@ -287,11 +427,14 @@ impl Ugc for Comment {
     fn get_is_private(&self) -> bool {
         false
     }
+    fn get_is_scheduled(&self) -> bool {
+        false
+    }
     fn get_is_deleted(&self) -> bool {
         self.is_deleted
     }
-    fn extra_delete_condition(&self) -> bool {
-        true
+    async fn extra_delete_condition(&self, _db: &Db, _rconn: &RdsConn) -> Api<bool> {
+        Ok(true)
     }
     async fn do_set_deleted(&mut self, db: &Db) -> Api<()> {
         update!(*self, comments, db, { is_deleted, to true });
@@ -305,12 +448,103 @@ macro_rules! look {
     };
 }
 
+// 声明表单或返回值的结构体，同时生成 OpenAPI 文档中的 schema
+// 字段上的文档注释作为说明，结构体后的 deprecated(...) 列出只为旧版前端保留的字段
+macro_rules! api_schema {
+    (@name $f:ident;) => {
+        stringify!($f)
+    };
+    (@name $f:ident; [field(name = $n:literal)] $($rest:tt)*) => {
+        $n
+    };
+    (@name $f:ident; [$($other:tt)*] $($rest:tt)*) => {
+        api_schema!(@name $f; $($rest)*)
+    };
+
+    (@docs [$($acc:expr),*];) => {
+        [$($acc),*]
+    };
+    (@docs [$($acc:expr),*]; [doc = $d:literal] $($rest:tt)*) => {
+        api_schema!(@docs [$($acc,)* $d]; $($rest)*)
+    };
+    (@docs [$($acc:expr),*]; [$($other:tt)*] $($rest:tt)*) => {
+        api_schema!(@docs [$($acc),*]; $($rest)*)
+    };
+
+    (@is_form form) => {
+        true
+    };
+    (@is_form output) => {
+        false
+    };
+
+    (#[derive(FromForm)] $($rest:tt)*) => {
+        api_schema!(@form #[derive(FromForm)] $($rest)*);
+    };
+    (#[derive(Serialize)] $($rest:tt)*) => {
+        api_schema!(@output #[derive(Serialize)] $($rest)*);
+    };
+    (
+        @$kind:ident
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$($fattr:tt)*])*
+                $fvis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+        $(deprecated($($dep:ident),* $(,)?))?
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$($fattr)*])*
+                $fvis $field: $ty,
+            )*
+        }
+
+        impl crate::api::openapi::ApiSchema for $name {
+            const NAME: &'static str = stringify!($name);
+
+            fn definition() -> rocket::serde::json::Value {
+                // 列出的字段必须存在
+                let _ = |_x: &Self| { $($(let _ = &_x.$dep;)*)? };
+                let deprecated: &[&str] = &[$($(stringify!($dep)),*)?];
+                crate::api::openapi::object(
+                    api_schema!(@is_form $kind),
+                    vec![$(
+                        crate::api::openapi::Field {
+                            name: api_schema!(@name $field; $([$($fattr)*])*),
+                            schema: <$ty as crate::api::openapi::TypeSchema>::schema(),
+                            optional: <$ty as crate::api::openapi::TypeSchema>::optional(),
+                            docs: &api_schema!(@docs []; $([$($fattr)*])*),
+                            deprecated: deprecated.contains(&stringify!($field)),
+                        }
+                    ),*],
+                )
+            }
+        }
+
+        impl crate::api::openapi::TypeSchema for $name {
+            fn schema() -> rocket::serde::json::Value {
+                crate::api::openapi::schema_ref::<$name>()
+            }
+        }
+    };
+}
+
 pub mod attention;
+pub mod cache;
 pub mod comment;
+pub mod draft;
+pub mod job;
+pub mod openapi;
 pub mod operation;
 pub mod post;
 pub mod reaction;
+pub mod room;
 pub mod search;
+pub mod slow_query;
 pub mod systemlog;
 pub mod upload;
 pub mod vote;
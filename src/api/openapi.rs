@@ -0,0 +1,380 @@
+// 根据挂载的路由生成 OpenAPI 文档，前端不必再从源码中猜接口
+// 路径和参数来自路由定义，表单和返回值的结构来自 api_schema! 声明的结构体
+use crate::api::comment::{CommentInput, CommentOutput};
+use crate::api::draft::{DraftInput, DraftTarget};
+use crate::api::operation::{
+    AutoBlockInput, BlockInput, DeleteInput, LangInput, ReportInput, TitleInput,
+};
+use crate::api::post::{CwInput, LockInput, PinInput, PostInput, PostOutput};
+use crate::api::reaction::{EmojiCount, EmojiInput, ReactionInput};
+use crate::api::room::RoomInput;
+use crate::api::vote::VoteInput;
+use crate::api::{attention::AttentionInput, attention::NotificatinInput};
+use rocket::http::Method;
+use rocket::serde::json::{json, Map, Value};
+use rocket::{Build, Rocket, State};
+
+pub trait TypeSchema {
+    fn schema() -> Value;
+
+    // 表单中可以不填
+    fn optional() -> bool {
+        false
+    }
+}
+
+pub trait ApiSchema {
+    const NAME: &'static str;
+
+    fn definition() -> Value;
+}
+
+macro_rules! primitive_schema {
+    ($($t:ty => $schema:tt),* $(,)?) => {
+        $(
+            impl TypeSchema for $t {
+                fn schema() -> Value {
+                    json!($schema)
+                }
+            }
+        )*
+    };
+}
+
+primitive_schema! {
+    i8 => {"type": "integer"},
+    u8 => {"type": "integer"},
+    i32 => {"type": "integer", "format": "int32"},
+    u32 => {"type": "integer"},
+    i64 => {"type": "integer", "format": "int64"},
+    String => {"type": "string"},
+    Value => {},
+}
+
+// 表单中未填写时为 false
+impl TypeSchema for bool {
+    fn schema() -> Value {
+        json!({"type": "boolean"})
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: TypeSchema> TypeSchema for Option<T> {
+    fn schema() -> Value {
+        annotate(T::schema(), "nullable", true.into())
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: TypeSchema> TypeSchema for Vec<T> {
+    fn schema() -> Value {
+        json!({"type": "array", "items": T::schema()})
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+pub struct Field {
+    pub name: &'static str,
+    pub schema: Value,
+    pub optional: bool,
+    pub docs: &'static [&'static str],
+    pub deprecated: bool,
+}
+
+pub fn schema_ref<T: ApiSchema>() -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", T::NAME) })
+}
+
+// $ref 旁边的其他字段会被忽略，需要包一层
+fn annotate(schema: Value, key: &str, value: Value) -> Value {
+    let mut schema = if schema.get("$ref").is_some() {
+        json!({ "allOf": [schema] })
+    } else {
+        schema
+    };
+    schema[key] = value;
+    schema
+}
+
+// 返回值中的字段总是存在(可能为 null)，表单中 Option、Vec 和 bool 可以不填
+pub fn object(is_form: bool, fields: Vec<Field>) -> Value {
+    let mut properties = Map::new();
+    let mut required = vec![];
+    for f in fields {
+        let mut schema = f.schema;
+        let docs: Vec<&str> = f.docs.iter().map(|d| d.trim()).collect();
+        if !docs.is_empty() {
+            schema = annotate(schema, "description", docs.join(" ").into());
+        }
+        if f.deprecated {
+            schema = annotate(schema, "deprecated", true.into());
+        }
+        if !(is_form && f.optional) {
+            required.push(f.name);
+        }
+        properties.insert(f.name.to_string(), schema);
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+macro_rules! components {
+    ($($t:ty),* $(,)?) => {{
+        let mut m = Map::new();
+        $(m.insert(<$t as ApiSchema>::NAME.to_string(), <$t as ApiSchema>::definition());)*
+        m
+    }};
+}
+
+fn request_body(route: &str) -> Option<Value> {
+    let form = match route {
+        "publish_post" => schema_ref::<PostInput>(),
+        "edit_cw" => schema_ref::<CwInput>(),
+        "lock_post" => schema_ref::<LockInput>(),
+        "pin_post" => schema_ref::<PinInput>(),
+        "add_comment" => schema_ref::<CommentInput>(),
+        "attention_post" => schema_ref::<AttentionInput>(),
+        "set_notification" => schema_ref::<NotificatinInput>(),
+        "reaction" => schema_ref::<ReactionInput>(),
+        "emoji_reaction" => schema_ref::<EmojiInput>(),
+        "vote" => schema_ref::<VoteInput>(),
+        "delete" => schema_ref::<DeleteInput>(),
+        "report" => schema_ref::<ReportInput>(),
+        "block" => schema_ref::<BlockInput>(),
+        "set_title" => schema_ref::<TitleInput>(),
+        "set_auto_block" => schema_ref::<AutoBlockInput>(),
+        "set_lang" => schema_ref::<LangInput>(),
+        "set_room" => schema_ref::<RoomInput>(),
+        "save_draft" => schema_ref::<DraftInput>(),
+        "delete_draft" => schema_ref::<DraftTarget>(),
+        "local_upload" => {
+            return Some(json!({
+                "required": true,
+                "content": {
+                    "application/octet-stream": {
+                        "schema": {"type": "string", "format": "binary"}
+                    }
+                }
+            }))
+        }
+        _ => return None,
+    };
+    Some(json!({
+        "required": true,
+        "content": {
+            "application/x-www-form-urlencoded": {"schema": form},
+            "multipart/form-data": {"schema": form},
+        }
+    }))
+}
+
+// code 为 0 时的返回值: data 的结构和 data 以外的字段
+fn response_fields(route: &str) -> (Value, Value) {
+    let posts = json!({"type": "array", "items": schema_ref::<PostOutput>()});
+    match route {
+        "get_list" => (
+            posts,
+            json!({
+                "count": {"type": "integer"},
+                "next_cursor": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "下一页的游标，原样作为 cursor 传回；为 null 时没有更多了",
+                },
+                "custom_title": {"type": "string", "nullable": true},
+                "title_secret": {"type": "string", "nullable": true},
+                "is_admin": {"type": "boolean"},
+                "is_candidate": {"type": "boolean"},
+                "auto_block_rank": {"type": "integer"},
+                "announcement": {"type": "string", "nullable": true},
+            }),
+        ),
+        "get_multi" | "get_attention" | "get_scheduled" | "search" => (posts, json!({})),
+        "get_one" => (schema_ref::<PostOutput>(), json!({})),
+        "get_comment" => (
+            json!({"type": "array", "items": schema_ref::<CommentOutput>()}),
+            json!({
+                "n_attentions": {"type": "integer"},
+                "attention": {"type": "boolean"},
+                "likenum": {"type": "integer", "deprecated": true, "description": "同 n_attentions"},
+            }),
+        ),
+        "attention_post" => (
+            json!({}),
+            json!({
+                "attention": {"type": "boolean"},
+                "n_attentions": {"type": "integer"},
+                "likenum": {"type": "integer", "deprecated": true, "description": "同 n_attentions"},
+            }),
+        ),
+        _ => (json!({}), json!({})),
+    }
+}
+
+fn response(route: &str) -> Value {
+    let (data, extra) = response_fields(route);
+    let mut properties = json!({
+        "code": {"type": "integer", "description": "成功时为 0"},
+        "data": data,
+    });
+    if let (Some(p), Value::Object(extra)) = (properties.as_object_mut(), extra) {
+        p.extend(extra);
+    }
+    json!({
+        "description": "成功",
+        "content": {
+            "application/json": {
+                "schema": {"type": "object", "properties": properties, "required": ["code"]}
+            }
+        }
+    })
+}
+
+fn error_response() -> Value {
+    json!({
+        "description": "失败，HTTP 状态码表示错误类别",
+        "content": {
+            "application/json": {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "code": {"type": "integer", "description": "固定为 -1"},
+                        "err": {"type": "string", "description": "错误标识，如 not_allowed、is_deleted"},
+                        "msg": {"type": "string", "description": "展示给用户的提示"},
+                    },
+                    "required": ["code", "err", "msg"]
+                }
+            }
+        }
+    })
+}
+
+fn dynamic(segment: &str) -> Option<&str> {
+    segment
+        .strip_prefix('<')?
+        .strip_suffix('>')
+        .map(|s| s.trim_end_matches(".."))
+}
+
+fn param(name: &str, location: &str) -> Value {
+    let schema = match name {
+        "pids" => json!({"type": "array", "items": {"type": "integer"}}),
+        "pid" | "id" | "p" | "page" | "order_mode" | "room_id" | "search_mode" => {
+            json!({"type": "integer"})
+        }
+        _ => json!({"type": "string"}),
+    };
+    json!({
+        "name": name,
+        "in": location,
+        "required": location == "path",
+        "schema": schema,
+    })
+}
+
+pub struct OpenApiDoc(Value);
+
+// 只包含 /_api/v1 和 /_api/v2 下的接口
+pub fn generate(rocket: &Rocket<Build>) -> OpenApiDoc {
+    let mut paths = Map::new();
+    for r in rocket
+        .routes()
+        .filter(|r| r.uri.base().starts_with("/_api/") && r.method != Method::Options)
+    {
+        let name = r.name.as_deref().unwrap_or_default();
+        let mut params = vec![];
+        let path = r
+            .uri
+            .path()
+            .split('/')
+            .map(|seg| match dynamic(seg) {
+                Some(p) => {
+                    params.push(param(p, "path"));
+                    format!("{{{}}}", p)
+                }
+                None => seg.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        params.extend(
+            r.uri
+                .query()
+                .into_iter()
+                .flat_map(|q| q.split('&'))
+                .filter_map(dynamic)
+                .map(|p| param(p, "query")),
+        );
+
+        let mut op = json!({
+            "operationId": name,
+            "tags": [r.uri.base().trim_start_matches("/_api/")],
+            "parameters": params,
+            "responses": {"200": response(name), "default": error_response()},
+        });
+        if let Some(body) = request_body(name) {
+            op["requestBody"] = body;
+        }
+        paths
+            .entry(path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap()
+            .insert(r.method.as_str().to_ascii_lowercase(), op);
+    }
+
+    OpenApiDoc(json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "hole-thu",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": components![
+                PostOutput,
+                CommentOutput,
+                EmojiCount,
+                PostInput,
+                CwInput,
+                LockInput,
+                PinInput,
+                CommentInput,
+                AttentionInput,
+                NotificatinInput,
+                ReactionInput,
+                EmojiInput,
+                VoteInput,
+                DeleteInput,
+                ReportInput,
+                BlockInput,
+                TitleInput,
+                AutoBlockInput,
+                LangInput,
+                RoomInput,
+                DraftInput,
+                DraftTarget,
+            ],
+            "securitySchemes": {
+                "UserToken": {"type": "apiKey", "in": "header", "name": "User-Token"}
+            }
+        },
+        "security": [{"UserToken": []}],
+    }))
+}
+
+#[get("/openapi.json")]
+pub fn openapi(doc: &State<OpenApiDoc>) -> Value {
+    doc.0.clone()
+}
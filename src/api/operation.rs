@@ -1,6 +1,7 @@
 use crate::api::{ApiError, CurrentUser, JsonApi, PolicyError::*, Ugc};
 use crate::cache::*;
 use crate::db_conn::Db;
+use crate::i18n::{self, Lang, Msg};
 use crate::models::*;
 use crate::rds_conn::RdsConn;
 use crate::rds_models::*;
@@ -8,12 +9,15 @@ use chrono::offset::Local;
 use rocket::form::Form;
 use rocket::serde::json::json;
 
-#[derive(FromForm)]
-pub struct DeleteInput {
-    #[field(name = "type")]
-    id_type: String,
-    id: i32,
-    note: String,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct DeleteInput {
+        /// pid 或 cid
+        #[field(name = "type")]
+        id_type: String,
+        id: i32,
+        note: String,
+    }
 }
 
 #[post("/delete", data = "<di>")]
@@ -21,7 +25,7 @@ pub async fn delete(di: Form<DeleteInput>, user: CurrentUser, db: Db, rconn: Rds
     let (author_hash, p) = match di.id_type.as_str() {
         "cid" => {
             let mut c = Comment::get(&db, di.id).await?;
-            c.soft_delete(&user, &db).await?;
+            c.soft_delete(&user, &db, &rconn).await?;
             let mut p = Post::get(&db, &rconn, c.post_id).await?;
             update!(
                 p,
@@ -45,10 +49,10 @@ pub async fn delete(di: Form<DeleteInput>, user: CurrentUser, db: Db, rconn: Rds
                     p,
                     posts,
                     &db,
-                    { content, to "[洞主已删除]" }
+                    { content, to Msg::OwnerDeleted.text(user.lang) }
                 }
             } else {
-                p.soft_delete(&user, &db).await?;
+                p.soft_delete(&user, &db, &rconn).await?;
             }
 
             // 如果是删除，需要也从0号缓存队列中去掉
@@ -87,12 +91,14 @@ pub async fn delete(di: Form<DeleteInput>, user: CurrentUser, db: Db, rconn: Rds
     code0!()
 }
 
-#[derive(FromForm)]
-pub struct ReportInput {
-    pid: i32,
-    #[field(validate = len(0..1000))]
-    reason: String,
-    should_hide: Option<u8>,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct ReportInput {
+        pid: i32,
+        #[field(validate = len(0..1000))]
+        reason: String,
+        should_hide: Option<u8>,
+    }
 }
 
 #[post("/report", data = "<ri>")]
@@ -126,32 +132,39 @@ pub async fn report(ri: Form<ReportInput>, user: CurrentUser, db: Db, rconn: Rds
     .create(&rconn)
     .await?;
 
-    // 自动发布一条洞
-    let p = Post::create(
-        &db,
-        NewPost {
-            content: format!("[系统自动代发]\n我举报了 #{}\n理由: {}", &p.id, &ri.reason),
-            cw: "举报".to_string(),
-            author_hash: user.namehash.clone(),
-            author_title: String::default(),
-            is_tmp: false,
-            n_attentions: 1,
-            allow_search: true,
-            room_id: 42,
-        },
-    )
-    .await?;
-    Attention::init(&user.namehash, &rconn).add(p.id).await?;
-    p.refresh_cache(&rconn, true).await;
+    // 自动发布一条洞到举报房间
+    if let Some(room) = Room::get_report_room(&db, &rconn).await? {
+        let p = Post::create(
+            &db,
+            NewPost {
+                content: i18n::report_post(user.lang, p.id, &ri.reason),
+                cw: Msg::ReportCw.text(user.lang).to_string(),
+                author_hash: user.namehash.clone(),
+                author_title: String::default(),
+                is_tmp: false,
+                n_attentions: 1,
+                allow_search: true,
+                room_id: room.id,
+                expire_time: None,
+                create_time: None,
+                is_scheduled: false,
+            },
+        )
+        .await?;
+        Attention::init(&user.namehash, &rconn).add(p.id).await?;
+        p.refresh_cache(&rconn, true).await;
+    }
 
     code0!()
 }
 
-#[derive(FromForm)]
-pub struct BlockInput {
-    #[field(name = "type")]
-    content_type: String,
-    id: i32,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct BlockInput {
+        #[field(name = "type")]
+        content_type: String,
+        id: i32,
+    }
 }
 
 #[post("/block", data = "<bi>")]
@@ -199,11 +212,13 @@ pub async fn block(bi: Form<BlockInput>, user: CurrentUser, db: Db, rconn: RdsCo
     }))
 }
 
-#[derive(FromForm)]
-pub struct TitleInput {
-    #[field(validate = len(1..31))]
-    title: String,
-    secret: String,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct TitleInput {
+        #[field(validate = len(1..31))]
+        title: String,
+        secret: String,
+    }
 }
 
 #[post("/set-title", data = "<ti>")]
@@ -223,9 +238,11 @@ pub async fn set_title(ti: Form<TitleInput>, user: CurrentUser, rconn: RdsConn)
     code0!(secret)
 }
 
-#[derive(FromForm)]
-pub struct AutoBlockInput {
-    rank: u8,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct AutoBlockInput {
+        rank: u8,
+    }
 }
 
 #[post("/auto_block", data = "<ai>")]
@@ -237,3 +254,22 @@ pub async fn set_auto_block(
     AutoBlockRank::set(&rconn, &user.namehash, ai.rank).await?;
     code0!()
 }
+
+api_schema! {
+    #[derive(FromForm)]
+    pub struct LangInput {
+        /// zh-CN 或 en，为空时按 Accept-Language 选择
+        lang: String,
+    }
+}
+
+#[post("/lang", data = "<li>")]
+pub async fn set_lang(li: Form<LangInput>, user: CurrentUser, rconn: RdsConn) -> JsonApi {
+    if li.lang.is_empty() {
+        LangSetting::clear(&rconn, &user.namehash).await?;
+        return code0!();
+    }
+    let lang = Lang::parse(&li.lang).ok_or(UnknownLang)?;
+    LangSetting::set(&rconn, &user.namehash, lang).await?;
+    code0!(lang.tag())
+}
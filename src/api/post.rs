@@ -1,68 +1,100 @@
 use crate::api::comment::{c2output, CommentOutput};
+use crate::api::reaction::{summarize_emoji, EmojiCount};
 use crate::api::vote::get_poll_dict;
 use crate::api::{Api, CurrentUser, JsonApi, PolicyError::*, Ugc};
 use crate::cache::*;
+use crate::config::AppConfig;
 use crate::db_conn::Db;
 use crate::models::*;
 use crate::rds_conn::RdsConn;
 use crate::rds_models::*;
+use chrono::offset::{Local, TimeZone, Utc};
 use rocket::form::Form;
 use rocket::futures::future::{self, OptionFuture};
 use rocket::serde::{
     json::{json, Value},
     Serialize,
 };
+use rocket::State;
 
-#[derive(FromForm)]
-pub struct PostInput {
-    #[field(validate = len(1..12289))]
-    text: String,
-    #[field(validate = len(0..97))]
-    cw: String,
-    allow_search: Option<i8>,
-    use_title: Option<i8>,
-    #[field(validate = len(0..97))]
-    poll_options: Vec<String>,
-    room_id: Option<i32>,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct PostInput {
+        #[field(validate = len(1..12289))]
+        text: String,
+        #[field(validate = len(0..97))]
+        cw: String,
+        /// 填写任意值表示允许搜索
+        allow_search: Option<i8>,
+        /// 填写任意值表示使用头衔
+        use_title: Option<i8>,
+        #[field(validate = len(0..97))]
+        poll_options: Vec<String>,
+        room_id: Option<i32>,
+        /// 自毁时间，单位为秒
+        expire_after: Option<i64>,
+        /// 定时发布的时间，unix 时间戳
+        publish_at: Option<i64>,
+    }
 }
 
-#[derive(Serialize)]
-#[serde(crate = "rocket::serde")]
-pub struct PostOutput {
-    pid: i32,
-    room_id: i32,
-    text: String,
-    cw: Option<String>,
-    author_title: Option<String>,
-    is_tmp: bool,
-    n_attentions: i32,
-    n_comments: i32,
-    create_time: i64,
-    last_comment_time: i64,
-    allow_search: bool,
-    is_reported: Option<bool>,
-    comments: Option<Vec<CommentOutput>>,
-    can_del: bool,
-    attention: bool,
-    hot_score: Option<i32>,
-    is_blocked: bool,
-    //blocked_count: Option<i32>,
-    poll: Option<Value>,
-    up_votes: i32,
-    down_votes: i32,
-    reaction_status: i32, // -1, 0, 1
-    // for old version frontend
-    timestamp: i64,
-    likenum: i32,
-    reply: i32,
-    blocked: bool,
+const MIN_EXPIRE_AFTER: i64 = 10 * 60;
+const MAX_EXPIRE_AFTER: i64 = 30 * 24 * 60 * 60;
+const MAX_SCHEDULE_AHEAD: i64 = 30 * 24 * 60 * 60;
+
+api_schema! {
+    #[derive(Serialize)]
+    #[serde(crate = "rocket::serde")]
+    pub struct PostOutput {
+        pid: i32,
+        room_id: i32,
+        text: String,
+        cw: Option<String>,
+        author_title: Option<String>,
+        is_tmp: bool,
+        n_attentions: i32,
+        n_comments: i32,
+        create_time: i64,
+        last_comment_time: i64,
+        allow_search: bool,
+        is_reported: Option<bool>,
+        comments: Option<Vec<CommentOutput>>,
+        can_del: bool,
+        attention: bool,
+        hot_score: Option<i32>,
+        is_blocked: bool,
+        //blocked_count: Option<i32>,
+        poll: Option<Value>,
+        up_votes: i32,
+        down_votes: i32,
+        /// -1, 0, 1
+        reaction_status: i32,
+        reactions: Vec<EmojiCount>,
+        my_reactions: Vec<String>,
+        is_pinned: bool,
+        is_locked: bool,
+        /// 剩余的存在时间，单位为秒
+        expire_in: Option<i64>,
+        // for old version frontend
+        /// 同 create_time
+        timestamp: i64,
+        /// 同 n_attentions
+        likenum: i32,
+        /// 同 n_comments
+        reply: i32,
+        /// 同 is_blocked
+        blocked: bool,
+    }
+    deprecated(timestamp, likenum, reply, blocked)
 }
 
-#[derive(FromForm)]
-pub struct CwInput {
-    pid: i32,
-    #[field(validate = len(0..97))]
-    cw: String,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct CwInput {
+        pid: i32,
+        #[field(validate = len(0..97))]
+        cw: String,
+    }
 }
 
 async fn p2output(p: &Post, user: &CurrentUser, db: &Db, rconn: &RdsConn) -> Api<PostOutput> {
@@ -84,6 +116,8 @@ async fn p2output(p: &Post, user: &CurrentUser, db: &Db, rconn: &RdsConn) -> Api
     let is_blocked = cached_block_dict[&p.author_hash];
     let can_view =
         user.is_admin || (!is_blocked && user.id.is_some() || user.namehash.eq(&p.author_hash));
+    let emoji_reactions = EmojiReaction::gets_by_post_id(db, rconn, p.id).await?;
+    let (reactions, my_reactions) = summarize_emoji(&emoji_reactions, 0, &user.namehash);
     Ok(PostOutput {
         pid: p.id,
         room_id: p.room_id,
@@ -97,12 +131,27 @@ async fn p2output(p: &Post, user: &CurrentUser, db: &Db, rconn: &RdsConn) -> Api
         author_title: (!p.author_title.is_empty()).then_some(p.author_title.clone()),
         is_tmp: p.is_tmp,
         is_reported: user.is_admin.then_some(p.is_reported),
-        comments: OptionFuture::from(
-            comments.map(|cs| async move { c2output(p, &cs, user, &cached_block_dict).await }),
-        )
+        comments: OptionFuture::from(comments.map(|cs| async move {
+            c2output(
+                p,
+                &cs,
+                user,
+                &cached_block_dict,
+                &emoji_reactions,
+                db,
+                rconn,
+            )
+            .await
+        }))
         .await,
-        can_del: p.check_permission(user, "wd").is_ok(),
-        attention: Attention::init(&user.namehash, rconn).has(p.id).await?,
+        can_del: p.check_delete_permission(user, db, rconn).await.is_ok(),
+        attention: Attention::init(&user.namehash, rconn)
+            .has(p.id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("get attention failed: {}", e);
+                false
+            }),
         hot_score: user.is_admin.then_some(p.hot_score),
         is_blocked,
         /*
@@ -113,13 +162,23 @@ async fn p2output(p: &Post, user: &CurrentUser, db: &Db, rconn: &RdsConn) -> Api
         },
         */
         poll: if can_view {
-            get_poll_dict(p.id, rconn, &user.namehash).await
+            get_poll_dict(p.id, db, rconn, &user.namehash).await?
         } else {
             None
         },
         up_votes: p.up_votes,
         down_votes: p.down_votes,
-        reaction_status: get_user_post_reaction_status(rconn, p.id, &user.namehash).await?,
+        reaction_status: get_user_post_reaction_status(rconn, p.id, &user.namehash)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("get reaction status failed: {}", e);
+                0
+            }),
+        reactions,
+        my_reactions,
+        is_pinned: false,
+        is_locked: p.is_locked,
+        expire_in: p.expire_time.map(|t| (t - Utc::now()).num_seconds().max(0)),
         // for old version frontend
         timestamp: p.create_time.timestamp(),
         likenum: p.n_attentions,
@@ -152,43 +211,77 @@ pub async fn get_one(pid: i32, user: CurrentUser, db: Db, rconn: RdsConn) -> Jso
     }))
 }
 
-#[get("/getlist?<p>&<order_mode>&<room_id>")]
+#[get("/getlist?<p>&<order_mode>&<room_id>&<cursor>")]
 pub async fn get_list(
     p: Option<u32>,
     order_mode: u8,
     room_id: Option<i32>,
+    cursor: Option<String>,
     user: CurrentUser,
     db: Db,
     rconn: RdsConn,
 ) -> JsonApi {
     user.id.ok_or(YouAreTmp)?;
-    let page = p.unwrap_or(1);
     let page_size = 25;
-    let start = (page - 1) * page_size;
-    let ps: Vec<Post> = Post::gets_by_page(
-        &db,
-        &rconn,
-        room_id,
-        order_mode,
-        start.into(),
-        page_size.into(),
-    )
-    .await?
-    .into_iter()
-    .filter(|post| page < 40 || !post.get_is_private())
-    .collect();
 
-    let ps_data = ps2outputs(&ps, &user, &db, &rconn).await?;
+    // 有游标时按游标翻页，第一页也按游标的顺序取，之后按页码翻页只为兼容旧版前端
+    let cursor = match cursor {
+        Some(c) => Some(ListCursor::decode(&c, order_mode).ok_or(InvalidCursor)?),
+        None if p.unwrap_or(1) == 1 && order_mode != 3 => Some(ListCursor::start(order_mode)),
+        None => None,
+    };
+    let page = cursor.map_or(p.unwrap_or(1), |c| c.page + 1);
+
+    // 置顶的洞显示在第一页最前面，并从正常的列表中去掉
+    let pinned_pids = PinnedPost::get_pids(&db, &rconn, room_id).await?;
+    let mut ps: Vec<Post> = if page == 1 {
+        Post::get_multi(&db, &rconn, &pinned_pids).await?
+    } else {
+        vec![]
+    };
+    let n_pinned = ps.len();
+
+    let (listed, next_cursor) = match &cursor {
+        Some(c) => Post::gets_after(&db, &rconn, room_id, c, page_size as usize).await?,
+        None => (
+            Post::gets_by_page(
+                &db,
+                &rconn,
+                room_id,
+                order_mode,
+                ((page - 1) * page_size).into(),
+                page_size.into(),
+            )
+            .await?,
+            None,
+        ),
+    };
+    ps.extend(
+        listed
+            .into_iter()
+            .filter(|post| !pinned_pids.contains(&post.id))
+            .filter(|post| page < 40 || !post.get_is_private()),
+    );
+
+    let mut ps_data = ps2outputs(&ps, &user, &db, &rconn).await?;
+    ps_data
+        .iter_mut()
+        .take(n_pinned)
+        .for_each(|po| po.is_pinned = true);
 
     Ok(json!({
         "data": ps_data,
         "count": ps_data.len(),
+        "next_cursor": next_cursor.map(|c| c.encode()),
         "custom_title": user.custom_title,
         "title_secret": user.title_secret,
         "is_admin": user.is_admin,
         "is_candidate": user.is_candidate,
         "auto_block_rank": user.auto_block_rank,
-        "announcement": get_announcement(&rconn).await?,
+        "announcement": get_announcement(&rconn).await.unwrap_or_else(|e| {
+            warn!("get announcement failed: {}", e);
+            None
+        }),
         "code": 0
     }))
 }
@@ -203,10 +296,38 @@ pub async fn publish_post(
     let use_title = poi.use_title.is_some() || user.is_admin || user.is_candidate;
 
     let is_tmp = user.id.is_none();
-    let room_id = if is_tmp {
-        0
-    } else {
-        poi.room_id.unwrap_or_default()
+    let mut room = Room::get(&db, &rconn, poi.room_id.unwrap_or_default()).await?;
+    if is_tmp && !room.allow_tmp {
+        // 旧版前端的临时用户总是发到0号房间
+        room = Room::get(&db, &rconn, 0).await?;
+        room.allow_tmp.then_some(()).ok_or(YouAreTmp)?;
+    }
+    if room.admin_only && !user.is_admin {
+        Err(NotAllowed)?;
+    }
+
+    let now = Utc::now();
+    let publish_time = match poi.publish_at {
+        Some(ts) => {
+            // 临时用户不能定时发布
+            user.id.ok_or(YouAreTmp)?;
+            (now.timestamp() < ts && ts <= now.timestamp() + MAX_SCHEDULE_AHEAD)
+                .then(|| Utc.timestamp_opt(ts, 0).single())
+                .flatten()
+                .ok_or(InvalidPublishTime)?
+        }
+        None => now,
+    };
+
+    let expire_time = match poi.expire_after {
+        Some(secs) => {
+            (MIN_EXPIRE_AFTER..=MAX_EXPIRE_AFTER)
+                .contains(&secs)
+                .then_some(())
+                .ok_or(InvalidExpireTime)?;
+            Some(publish_time + chrono::Duration::seconds(secs))
+        }
+        None => None,
     };
 
     let p = Post::create(
@@ -222,21 +343,47 @@ pub async fn publish_post(
             is_tmp,
             n_attentions: 1,
             allow_search: poi.allow_search.is_some(),
-            room_id,
+            room_id: room.id,
+            expire_time,
+            create_time: poi.publish_at.map(|_| publish_time),
+            is_scheduled: poi.publish_at.is_some(),
         },
     )
     .await?;
     Attention::init(&user.namehash, &rconn).add(p.id).await?;
-    p.refresh_cache(&rconn, true).await;
+    if p.is_scheduled {
+        p.set_instance_cache(&rconn).await;
+    } else {
+        p.refresh_cache(&rconn, true).await;
+    }
 
     if !poi.poll_options.is_empty() {
-        PollOption::init(p.id, &rconn)
-            .set_list(&poi.poll_options)
-            .await?;
+        Poll::create(&db, p.id, poi.poll_options.clone()).await?;
     }
     code0!()
 }
 
+#[get("/scheduled")]
+pub async fn get_scheduled(user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
+    user.id.ok_or(YouAreTmp)?;
+    let ps = Post::gets_scheduled_by_author(&db, &user.namehash).await?;
+    let ps_data = ps2outputs(&ps, &user, &db, &rconn).await?;
+
+    code0!(ps_data)
+}
+
+#[post("/scheduled/<pid>/cancel")]
+pub async fn cancel_scheduled(pid: i32, user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
+    let mut p = Post::get(&db, &rconn, pid).await?;
+    p.check_permission(&user, "rw")?;
+    p.is_scheduled.then_some(()).ok_or(NotAllowed)?;
+
+    update!(p, posts, &db, { is_deleted, to true });
+    p.set_instance_cache(&rconn).await;
+
+    code0!()
+}
+
 #[post("/editcw", data = "<cwi>")]
 pub async fn edit_cw(cwi: Form<CwInput>, user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
     let mut p = Post::get(&db, &rconn, cwi.pid).await?;
@@ -246,6 +393,61 @@ pub async fn edit_cw(cwi: Form<CwInput>, user: CurrentUser, db: Db, rconn: RdsCo
     code0!()
 }
 
+api_schema! {
+    #[derive(FromForm)]
+    pub struct LockInput {
+        #[field(validate = range(0..2))]
+        switch: i32,
+        #[field(validate = len(0..1000))]
+        note: String,
+    }
+}
+
+#[post("/post/<pid>/lock", data = "<li>")]
+pub async fn lock_post(
+    pid: i32,
+    li: Form<LockInput>,
+    user: CurrentUser,
+    db: Db,
+    rconn: RdsConn,
+    config: &State<AppConfig>,
+) -> JsonApi {
+    let mut p = Post::get(&db, &rconn, pid).await?;
+    p.check_permission(&user, "r")?;
+    if !user.is_admin {
+        if !config.author_can_lock {
+            Err(NotAllowed)?;
+        }
+        p.check_permission(&user, "w")?;
+    }
+
+    let switch_to = li.switch == 1;
+    if p.is_locked != switch_to {
+        update!(p, posts, &db, { is_locked, to switch_to });
+        p.refresh_cache(&rconn, false).await;
+
+        Systemlog {
+            user_hash: if user.is_admin {
+                user.custom_title.clone().unwrap_or(look!(user.namehash))
+            } else {
+                "洞主".to_string()
+            },
+            action_type: LogType::Lock,
+            target: format!("#{}", p.id),
+            detail: format!(
+                "{}\n{}",
+                if switch_to { "锁定" } else { "解除锁定" },
+                &li.note
+            ),
+            time: Local::now(),
+        }
+        .create(&rconn)
+        .await?;
+    }
+
+    code0!()
+}
+
 #[get("/getmulti?<pids>")]
 pub async fn get_multi(pids: Vec<i32>, user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
     user.id.ok_or(YouAreTmp)?;
@@ -264,3 +466,54 @@ pub async fn get_multi(pids: Vec<i32>, user: CurrentUser, db: Db, rconn: RdsConn
         "data": ps_data,
     }))
 }
+
+api_schema! {
+    #[derive(FromForm)]
+    pub struct PinInput {
+        pid: i32,
+        /// 置顶到全部房间的列表，否则置顶到洞所在的房间
+        global: bool,
+        expire_hours: Option<u32>,
+        #[field(validate = range(0..2))]
+        switch: i32,
+    }
+}
+
+#[post("/admin/pin", data = "<pi>")]
+pub async fn pin_post(
+    pi: Form<PinInput>,
+    user: CurrentUser,
+    db: Db,
+    rconn: RdsConn,
+    config: &State<AppConfig>,
+) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    if pi.switch == 0 {
+        PinnedPost::unpin(&db, &rconn, pi.pid).await?;
+        return code0!();
+    }
+
+    let p = Post::get(&db, &rconn, pi.pid).await?;
+    p.check_permission(&user, "r")?;
+
+    let now = Utc::now();
+    let pinned = PinnedPost::pin(
+        &db,
+        &rconn,
+        PinnedPost {
+            post_id: p.id,
+            room_id: (!pi.global).then_some(p.room_id),
+            pinned_by: user.namehash.clone(),
+            create_time: now,
+            expire_time: pi
+                .expire_hours
+                .map(|h| now + chrono::Duration::hours(h.into())),
+        },
+        config.max_pinned_posts,
+    )
+    .await?;
+    pinned.then_some(()).ok_or(TooManyPinned)?;
+
+    code0!()
+}
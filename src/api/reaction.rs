@@ -1,15 +1,19 @@
 use crate::api::{CurrentUser, JsonApi, PolicyError::*, Ugc};
+use crate::config::AppConfig;
 use crate::db_conn::Db;
 use crate::models::*;
 use crate::rds_conn::RdsConn;
 use crate::rds_models::*;
 use rocket::form::Form;
-use rocket::serde::json::json;
+use rocket::serde::{json::json, Serialize};
+use rocket::State;
 
-#[derive(FromForm)]
-pub struct ReactionInput {
-    #[field(validate = range(-1..2))]
-    status: i32,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct ReactionInput {
+        #[field(validate = range(-1..2))]
+        status: i32,
+    }
 }
 
 #[post("/post/<pid>/reaction", data = "<ri>")]
@@ -63,3 +67,99 @@ pub async fn reaction(
         },
     }))
 }
+
+api_schema! {
+    #[derive(Serialize)]
+    #[serde(crate = "rocket::serde")]
+    pub struct EmojiCount {
+        emoji: String,
+        count: i32,
+    }
+}
+
+// 统计某条洞(cid为0)或评论的表情数，以及当前用户点过的表情
+pub fn summarize_emoji(
+    rs: &[EmojiReaction],
+    cid: i32,
+    namehash: &str,
+) -> (Vec<EmojiCount>, Vec<String>) {
+    let mut counts: Vec<EmojiCount> = vec![];
+    let mut mine: Vec<String> = vec![];
+    for r in rs.iter().filter(|r| r.comment_id == cid) {
+        match counts.iter_mut().find(|ec| ec.emoji.eq(&r.emoji)) {
+            Some(ec) => ec.count += 1,
+            None => counts.push(EmojiCount {
+                emoji: r.emoji.clone(),
+                count: 1,
+            }),
+        }
+        if r.user_hash.eq(namehash) {
+            mine.push(r.emoji.clone());
+        }
+    }
+    (counts, mine)
+}
+
+api_schema! {
+    #[derive(FromForm)]
+    pub struct EmojiInput {
+        emoji: String,
+        cid: Option<i32>,
+        #[field(validate = range(0..2))]
+        switch: i32,
+    }
+}
+
+#[post("/post/<pid>/emoji", data = "<ei>")]
+pub async fn emoji_reaction(
+    pid: i32,
+    ei: Form<EmojiInput>,
+    user: CurrentUser,
+    db: Db,
+    rconn: RdsConn,
+    config: &State<AppConfig>,
+) -> JsonApi {
+    user.id.ok_or(YouAreTmp)?;
+
+    if !config.emoji_set.contains(&ei.emoji) {
+        Err(UnknownEmoji)?;
+    }
+
+    let p = Post::get(&db, &rconn, pid).await?;
+    p.check_permission(&user, "r")?;
+
+    let cid = ei.cid.unwrap_or_default();
+    if cid != 0 {
+        let c = Comment::get(&db, cid).await?;
+        c.check_permission(&user, "r")?;
+        if c.post_id != pid {
+            Err(NotAllowed)?;
+        }
+    }
+
+    EmojiReaction::set(
+        &db,
+        &rconn,
+        NewEmojiReaction {
+            post_id: pid,
+            comment_id: cid,
+            user_hash: user.namehash.clone(),
+            emoji: ei.emoji.clone(),
+        },
+        ei.switch == 1,
+    )
+    .await?;
+
+    let rs = EmojiReaction::gets_by_post_id(&db, &rconn, pid).await?;
+    let (reactions, my_reactions) = summarize_emoji(&rs, cid, &user.namehash);
+
+    code0!(json!({
+        "reactions": reactions,
+        "my_reactions": my_reactions,
+    }))
+}
+
+#[get("/emoji_set")]
+pub async fn get_emoji_set(_user: CurrentUser, config: &State<AppConfig>) -> JsonApi {
+    code0!(config.emoji_set)
+}
@@ -0,0 +1,67 @@
+use crate::api::{CurrentUser, JsonApi, PolicyError::*};
+use crate::db_conn::Db;
+use crate::models::*;
+use crate::rds_conn::RdsConn;
+use rocket::form::Form;
+use rocket::serde::json::json;
+
+#[get("/rooms")]
+pub async fn get_rooms(user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
+    let rooms: Vec<Room> = Room::all(&db, &rconn)
+        .await?
+        .into_iter()
+        .filter(|r| user.is_admin || r.is_visible)
+        .collect();
+
+    code0!(rooms)
+}
+
+api_schema! {
+    #[derive(FromForm)]
+    pub struct RoomInput {
+        id: Option<i32>,
+        #[field(validate = len(1..31))]
+        name: String,
+        #[field(validate = len(0..1000))]
+        description: String,
+        is_visible: bool,
+        admin_only: bool,
+        allow_delete: bool,
+        allow_tmp: bool,
+        accept_reports: bool,
+    }
+}
+
+#[post("/admin/room", data = "<ri>")]
+pub async fn set_room(ri: Form<RoomInput>, user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    let ri = ri.into_inner();
+    let new_room = NewRoom {
+        name: ri.name,
+        description: ri.description,
+        is_visible: ri.is_visible,
+        admin_only: ri.admin_only,
+        allow_delete: ri.allow_delete,
+        allow_tmp: ri.allow_tmp,
+        accept_reports: ri.accept_reports,
+    };
+    let room = match ri.id {
+        Some(id) => Room::update(&db, &rconn, id, new_room).await?,
+        None => Room::create(&db, &rconn, new_room).await?,
+    };
+
+    code0!(room)
+}
+
+#[post("/admin/room/<id>/delete")]
+pub async fn delete_room(id: i32, user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    // 还有洞的房间不能删除，可以设为不可见
+    if !Room::delete(&db, &rconn, id).await? {
+        Err(NotAllowed)?;
+    }
+
+    code0!()
+}
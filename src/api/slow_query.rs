@@ -0,0 +1,19 @@
+use crate::api::{CurrentUser, JsonApi, PolicyError::*};
+use crate::libs::diesel_logger;
+use rocket::serde::json::json;
+
+#[get("/admin/slow_queries")]
+pub async fn get_slow_queries(user: CurrentUser) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    code0!(diesel_logger::slow_queries())
+}
+
+#[post("/admin/slow_queries/reset")]
+pub async fn reset_slow_queries(user: CurrentUser) -> JsonApi {
+    user.is_admin.then_some(()).ok_or(NotAllowed)?;
+
+    diesel_logger::reset_slow_queries();
+
+    code0!()
+}
@@ -1,10 +1,15 @@
 use super::{CurrentUser, JsonApi};
+use crate::config::AppConfig;
 use rocket::fs::TempFile;
 use rocket::serde::json::json;
-use std::env::var;
+use rocket::State;
 
 #[post("/upload", data = "<file>")]
-pub async fn local_upload(_user: CurrentUser, mut file: TempFile<'_>) -> JsonApi {
+pub async fn local_upload(
+    _user: CurrentUser,
+    mut file: TempFile<'_>,
+    config: &State<AppConfig>,
+) -> JsonApi {
     let filename: String = format!(
         "file{}.{}",
         file.path().unwrap().file_name().unwrap().to_str().unwrap(),
@@ -13,7 +18,7 @@ pub async fn local_upload(_user: CurrentUser, mut file: TempFile<'_>) -> JsonApi
             .unwrap_or("unknown")
     );
 
-    file.copy_to(format!("{}/{}", var("UPLOAD_DIR").unwrap(), filename))
+    file.copy_to(format!("{}/{}", config.upload_dir, filename))
         .await?;
 
     code0!(json!({ "path": filename }))
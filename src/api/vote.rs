@@ -1,69 +1,127 @@
-use crate::api::{CurrentUser, JsonApi, PolicyError::*};
+use crate::api::{Api, CurrentUser, JsonApi, PolicyError::*, Ugc};
+use crate::db_conn::Db;
+use crate::models::*;
 use crate::rds_conn::RdsConn;
-use crate::rds_models::*;
 use rocket::form::Form;
-use rocket::futures::future;
-use rocket::serde::json::{json, Value};
+use rocket::http::{ContentType, Header};
+use rocket::serde::json::{json, serde_json, Value};
 
-pub async fn get_poll_dict(pid: i32, rconn: &RdsConn, namehash: &str) -> Option<Value> {
-    let opts = PollOption::init(pid, rconn)
-        .get_list()
-        .await
-        .unwrap_or_default();
-    if opts.is_empty() {
-        None
-    } else {
-        let choice = future::join_all(opts.iter().enumerate().map(|(idx, opt)| async move {
-            PollVote::init(pid, idx, rconn)
-                .has(namehash)
-                .await
-                .unwrap_or_default()
-                .then_some(opt)
-        }))
-        .await
-        .into_iter()
-        .flatten()
-        .collect::<Vec<&String>>()
-        .pop();
-        Some(json!({
-            "answers": future::join_all(
-                opts.iter().enumerate().map(|(idx, opt)| async move {
-                    json!({
-                        "option": opt,
-                        "votes": PollVote::init(pid, idx, rconn).count().await.unwrap_or_default(),
-                    })
-                })
-                    ).await,
-            "vote": choice,
-        }))
-    }
+pub async fn get_poll_dict(
+    pid: i32,
+    db: &Db,
+    rconn: &RdsConn,
+    namehash: &str,
+) -> Api<Option<Value>> {
+    Ok(Poll::get_data(db, rconn, pid).await?.map(|pd| {
+        json!({
+            "answers": pd.options.iter().zip(pd.votes.iter()).map(|(opt, votes)| json!({
+                "option": opt.content,
+                "votes": votes,
+            })).collect::<Vec<Value>>(),
+            "vote": pd.voters.get(namehash).map(|idx| &pd.options[*idx].content),
+        })
+    }))
 }
 
-#[derive(FromForm)]
-pub struct VoteInput {
-    pid: i32,
-    vote: String,
+api_schema! {
+    #[derive(FromForm)]
+    pub struct VoteInput {
+        pid: i32,
+        vote: String,
+    }
 }
 
 #[post("/vote", data = "<vi>")]
-pub async fn vote(vi: Form<VoteInput>, user: CurrentUser, rconn: RdsConn) -> JsonApi {
+pub async fn vote(vi: Form<VoteInput>, user: CurrentUser, db: Db, rconn: RdsConn) -> JsonApi {
     user.id.ok_or(NotAllowed)?;
 
-    let pid = vi.pid;
-    let opts = PollOption::init(pid, &rconn).get_list().await?;
-    if opts.is_empty() {
+    let pd = Poll::get_data(&db, &rconn, vi.pid)
+        .await?
+        .ok_or(NotAllowed)?;
+
+    if pd.voters.contains_key(&user.namehash) {
+        Err(NotAllowed)?;
+    }
+
+    let idx: usize = pd
+        .options
+        .iter()
+        .position(|opt| opt.content.eq(&vi.vote))
+        .ok_or(NotAllowed)?;
+
+    if !pd.add_vote(&db, &rconn, idx, &user.namehash).await? {
         Err(NotAllowed)?;
     }
 
-    for idx in 0..opts.len() {
-        if PollVote::init(pid, idx, &rconn).has(&user.namehash).await? {
-            Err(NotAllowed)?;
-        }
+    code0!(get_poll_dict(vi.pid, &db, &rconn, &user.namehash).await?)
+}
+
+#[derive(Responder)]
+pub struct PollExport {
+    inner: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\r', '\n'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[get("/post/<pid>/poll/export?<format>")]
+pub async fn export_poll(
+    pid: i32,
+    format: Option<String>,
+    user: CurrentUser,
+    db: Db,
+    rconn: RdsConn,
+) -> Api<PollExport> {
+    let p = Post::get(&db, &rconn, pid).await?;
+    p.check_permission(&user, "r")?;
+    // 只有洞主可以导出
+    if p.author_hash != user.namehash {
+        Err(NotAllowed)?;
     }
 
-    let idx: usize = opts.iter().position(|x| x.eq(&vi.vote)).ok_or(NotAllowed)?;
+    let pd = Poll::get_data(&db, &rconn, pid)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    let results = pd.options.iter().zip(pd.votes.iter());
 
-    PollVote::init(pid, idx, &rconn).add(&user.namehash).await?;
+    let (content_type, ext, body) = match format.as_deref().unwrap_or("json") {
+        "csv" => (
+            ContentType::CSV,
+            "csv",
+            std::iter::once("idx,option,votes\n".to_string())
+                .chain(results.map(|(opt, votes)| {
+                    format!("{},{},{}\n", opt.idx, csv_field(&opt.content), votes)
+                }))
+                .collect::<String>(),
+        ),
+        "json" => (
+            ContentType::JSON,
+            "json",
+            serde_json::to_string(&json!({
+                "pid": pid,
+                "total": pd.votes.iter().sum::<i64>(),
+                "results": results.map(|(opt, votes)| json!({
+                    "idx": opt.idx,
+                    "option": opt.content,
+                    "votes": votes,
+                })).collect::<Vec<Value>>(),
+            }))
+            .unwrap(),
+        ),
+        _ => Err(InvalidFormat)?,
+    };
 
-    code0!(get_poll_dict(vi.pid, &rconn, &user.namehash).await)
+    Ok(PollExport {
+        inner: (content_type, body),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"poll_{}.{}\"", pid, ext),
+        ),
+    })
 }
@@ -1,7 +1,7 @@
 use crate::api::{Api, CurrentUser};
 use crate::db_conn::Db;
-use crate::models::{Comment, Post, User};
-use crate::rds_conn::RdsConn;
+use crate::models::{Comment, EmojiReaction, HotDecay, PinnedPost, PollData, Post, Room, User};
+use crate::rds_conn::{rds_available, RdsConn};
 use crate::rds_models::{clear_all, init, BlockedUsers};
 use rand::Rng;
 use redis::{AsyncCommands, RedisError, RedisResult};
@@ -9,9 +9,14 @@ use rocket::serde::json::serde_json;
 // can use rocket::serde::json::to_string in master version
 use futures_util::stream::StreamExt;
 use rocket::futures::future;
+use rocket::serde::json::{json, Value};
+use rocket::serde::Serialize;
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 const KEY_USER_COUNT: &str = "hole_v2:cache:user_count";
+const KEY_ROOMS: &str = "hole_v2:cache:rooms";
 const USER_COUNT_EXPIRE_TIME: usize = 5 * 60;
 
 const INSTANCE_EXPIRE_TIME: usize = 60 * 60;
@@ -20,6 +25,88 @@ const MIN_LENGTH: isize = 200;
 const MAX_LENGTH: isize = 900;
 const CUT_LENGTH: isize = 100;
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CacheMetricsSnapshot {
+    pub name: &'static str,
+    pub hits: u64,
+    pub misses: u64,
+    pub fills: u64,
+    pub evictions: u64,
+}
+
+pub struct CacheMetrics {
+    name: &'static str,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    fills: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl CacheMetrics {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            fills: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn hit(&self, n: usize) {
+        self.hits.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn miss(&self, n: usize) {
+        self.misses.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn fill(&self, n: usize) {
+        self.fills.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn evict(&self, n: usize) {
+        self.evictions.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn record<T>(&self, r: &Option<T>) {
+        match r {
+            Some(_) => self.hit(1),
+            None => self.miss(1),
+        }
+    }
+
+    pub fn snapshot(&self) -> CacheMetricsSnapshot {
+        CacheMetricsSnapshot {
+            name: self.name,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            fills: self.fills.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static POST_METRICS: CacheMetrics = CacheMetrics::new("post");
+static POST_COMMENT_METRICS: CacheMetrics = CacheMetrics::new("post_comment");
+static POST_LIST_METRICS: CacheMetrics = CacheMetrics::new("post_list");
+static USER_METRICS: CacheMetrics = CacheMetrics::new("user");
+static BLOCK_DICT_METRICS: CacheMetrics = CacheMetrics::new("block_dict");
+
+pub fn cache_metrics() -> Vec<CacheMetricsSnapshot> {
+    [
+        &POST_METRICS,
+        &POST_COMMENT_METRICS,
+        &POST_LIST_METRICS,
+        &USER_METRICS,
+        &BLOCK_DICT_METRICS,
+    ]
+    .iter()
+    .map(|m| m.snapshot())
+    .collect()
+}
+
 macro_rules! post_cache_key {
     ($id: expr) => {
         format!("hole_v2:cache:post:{}:v2", $id)
@@ -33,7 +120,7 @@ pub struct PostCache {
 impl PostCache {
     init!();
 
-    clear_all!("hole_v2:cache::post:*:v2");
+    clear_all!("hole_v2:cache:post:*:v2", POST_METRICS);
 
     pub async fn sets(&mut self, ps: &[&Post]) {
         if ps.is_empty() {
@@ -43,10 +130,14 @@ impl PostCache {
             .iter()
             .map(|p| (post_cache_key!(p.id), serde_json::to_string(p).unwrap()))
             .collect();
-        self.rconn.mset(&kvs).await.unwrap_or_else(|e| {
-            warn!("set post cache failed: {}", e);
-            dbg!(&kvs);
-        });
+        self.rconn
+            .mset(&kvs)
+            .await
+            .map(|()| POST_METRICS.fill(kvs.len()))
+            .unwrap_or_else(|e| {
+                warn!("set post cache failed: {}", e);
+                dbg!(&kvs);
+            });
     }
 
     pub async fn get(&mut self, pid: &i32) -> Option<Post> {
@@ -60,12 +151,14 @@ impl PostCache {
                 None
             });
 
-        rds_result.and_then(|s| {
+        let p = rds_result.and_then(|s| {
             serde_json::from_str(&s).unwrap_or_else(|e| {
                 warn!("get post cache, decode failed {}, {}", e, s);
                 None
             })
-        })
+        });
+        POST_METRICS.record(&p);
+        p
     }
 
     pub async fn gets(&mut self, pids: &[i32]) -> Vec<Option<Post>> {
@@ -93,12 +186,14 @@ impl PostCache {
                     .into_iter()
                     .map(|x| {
                         // dbg!(&x);
-                        x.and_then(|s| {
+                        let p = x.and_then(|s| {
                             serde_json::from_str(&s).unwrap_or_else(|e| {
                                 warn!("get post cache, decode failed {}, {}", e, s);
                                 None
                             })
-                        })
+                        });
+                        POST_METRICS.record(&p);
+                        p
                     })
                     .collect()
             }
@@ -114,6 +209,8 @@ pub struct PostCommentCache {
 impl PostCommentCache {
     init!(i32, "hole_v2:cache:post_comments:{}");
 
+    clear_all!("hole_v2:cache:post_comments:*", POST_COMMENT_METRICS);
+
     pub async fn set(&mut self, cs: &[Comment]) {
         self.rconn
             .set_ex(
@@ -122,6 +219,7 @@ impl PostCommentCache {
                 INSTANCE_EXPIRE_TIME,
             )
             .await
+            .map(|()| POST_COMMENT_METRICS.fill(1))
             .unwrap_or_else(|e| {
                 warn!("set comments cache failed: {}", e);
                 dbg!(cs);
@@ -129,6 +227,12 @@ impl PostCommentCache {
     }
 
     pub async fn get(&mut self) -> Option<Vec<Comment>> {
+        let cs = self._get().await;
+        POST_COMMENT_METRICS.record(&cs);
+        cs
+    }
+
+    async fn _get(&mut self) -> Option<Vec<Comment>> {
         let rds_result = self.rconn.get::<&String, String>(&self.key).await;
         // dbg!(&rds_result);
         if let Ok(s) = rds_result {
@@ -151,9 +255,100 @@ impl PostCommentCache {
         }
     }
 
+    pub async fn clear(&mut self) {
+        self.rconn
+            .del(&self.key)
+            .await
+            .map(|n| POST_COMMENT_METRICS.evict(n))
+            .unwrap_or_else(|e| {
+                warn!("clear commenrs cache fail, {}", e);
+            });
+    }
+}
+
+pub struct PollCache {
+    key: String,
+    rconn: RdsConn,
+}
+
+impl PollCache {
+    init!(i32, "hole_v2:cache:poll:{}");
+
+    clear_all!("hole_v2:cache:poll:*");
+
+    // 没有投票的洞也缓存(为null)，避免每次都查库
+    pub async fn set(&mut self, pd: &Option<PollData>) {
+        self.rconn
+            .set_ex(
+                &self.key,
+                serde_json::to_string(pd).unwrap(),
+                INSTANCE_EXPIRE_TIME,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                warn!("set poll cache failed: {}", e);
+                dbg!(pd);
+            })
+    }
+
+    pub async fn get(&mut self) -> Option<Option<PollData>> {
+        let rds_result = self.rconn.get::<&String, String>(&self.key).await;
+        if let Ok(s) = rds_result {
+            serde_json::from_str(&s).map(Some).unwrap_or_else(|e| {
+                warn!("get poll cache, decode failed {}, {}", e, s);
+                None
+            })
+        } else {
+            None
+        }
+    }
+
+    pub async fn clear(&mut self) {
+        self.rconn.del(&self.key).await.unwrap_or_else(|e| {
+            warn!("clear poll cache fail, {}", e);
+        });
+    }
+}
+
+pub struct EmojiReactionCache {
+    key: String,
+    rconn: RdsConn,
+}
+
+impl EmojiReactionCache {
+    init!(i32, "hole_v2:cache:emoji_reactions:{}");
+
+    clear_all!("hole_v2:cache:emoji_reactions:*");
+
+    pub async fn set(&mut self, rs: &[EmojiReaction]) {
+        self.rconn
+            .set_ex(
+                &self.key,
+                serde_json::to_string(rs).unwrap(),
+                INSTANCE_EXPIRE_TIME,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                warn!("set emoji reactions cache failed: {}", e);
+                dbg!(rs);
+            })
+    }
+
+    pub async fn get(&mut self) -> Option<Vec<EmojiReaction>> {
+        let rds_result = self.rconn.get::<&String, String>(&self.key).await;
+        if let Ok(s) = rds_result {
+            serde_json::from_str(&s).unwrap_or_else(|e| {
+                warn!("get emoji reactions cache, decode failed {}, {}", e, s);
+                None
+            })
+        } else {
+            None
+        }
+    }
+
     pub async fn clear(&mut self) {
         self.rconn.del(&self.key).await.unwrap_or_else(|e| {
-            warn!("clear commenrs cache fail, {}", e);
+            warn!("clear emoji reactions cache fail, {}", e);
         });
     }
 }
@@ -161,11 +356,14 @@ impl PostCommentCache {
 pub struct PostListCache {
     key: String,
     mode: u8,
+    decay: Option<HotDecay>,
     rconn: RdsConn,
     length: isize,
 }
 
 impl PostListCache {
+    clear_all!("hole_v2:cache:post_list:*", POST_LIST_METRICS);
+
     pub fn init(room_id: Option<i32>, mode: u8, rconn: &RdsConn) -> Self {
         Self {
             key: format!(
@@ -177,28 +375,38 @@ impl PostListCache {
                 &mode
             ),
             mode,
+            decay: (mode == 5).then(HotDecay::current),
             rconn: rconn.clone(),
             length: 0,
         }
     }
 
-    async fn set_and_check_length(&mut self) {
-        let mut l = self.rconn.zcard(&self.key).await.unwrap();
+    async fn set_and_check_length(&mut self) -> RedisResult<()> {
+        let mut l = self.rconn.zcard(&self.key).await?;
         if l > MAX_LENGTH {
             self.rconn
-                .zremrangebyrank::<&String, ()>(&self.key, MAX_LENGTH - CUT_LENGTH, -1)
+                .zremrangebyrank::<&String, usize>(&self.key, MAX_LENGTH - CUT_LENGTH, -1)
                 .await
+                .map(|n| POST_LIST_METRICS.evict(n))
                 .unwrap_or_else(|e| {
                     warn!("cut list cache failed, {}, {}", e, &self.key);
                 });
             l = MIN_LENGTH;
         }
         self.length = l;
+        Ok(())
     }
 
+    // redis 不可用时不填充，长度视为0，之后直接查数据库
     pub async fn need_fill(&mut self) -> bool {
-        self.set_and_check_length().await;
-        self.length < MIN_LENGTH
+        match self.set_and_check_length().await {
+            Ok(()) => self.length < MIN_LENGTH && rds_available(),
+            Err(e) => {
+                warn!("get list cache length failed, {}, {}", e, &self.key);
+                self.length = 0;
+                false
+            }
+        }
     }
 
     pub fn i64_len(&self) -> i64 {
@@ -212,12 +420,8 @@ impl PostListCache {
     fn p2pair(&self, p: &Post) -> (i64, i32) {
         (
             match self.mode {
-                0 => (-p.id).into(),
-                1 => -p.last_comment_time.timestamp(),
-                2 => (-p.hot_score).into(),
                 3 => rand::thread_rng().gen_range(0..i64::MAX),
-                4 => (-p.n_attentions).into(),
-                _ => panic!("wrong mode"),
+                mode => p.list_key(mode, self.decay).expect("wrong mode"),
             },
             p.id,
         )
@@ -228,23 +432,31 @@ impl PostListCache {
         self.rconn
             .zadd_multiple(&self.key, &items)
             .await
+            .map(|()| POST_LIST_METRICS.fill(items.len()))
             .unwrap_or_else(|e| {
                 warn!("fill list cache failed, {} {}", e, &self.key);
             });
 
-        self.set_and_check_length().await;
+        self.set_and_check_length().await.unwrap_or_else(|e| {
+            warn!("get list cache length failed, {}, {}", e, &self.key);
+            self.length = 0;
+        });
     }
 
     pub async fn put(&mut self, p: &Post) {
         // 其他都是加到最前面的，但热榜不是。可能导致MIN_LENGTH到MAX_LENGTH之间的数据不可靠
         // 影响不大，先不管了
-        if p.is_deleted || (self.mode > 0 && p.is_reported) {
-            self.rconn.zrem(&self.key, p.id).await.unwrap_or_else(|e| {
-                warn!(
-                    "remove from list cache failed, {} {} {}",
-                    e, &self.key, p.id
-                );
-            });
+        if p.is_deleted || p.is_scheduled || (self.mode > 0 && p.is_reported) {
+            self.rconn
+                .zrem(&self.key, p.id)
+                .await
+                .map(|n| POST_LIST_METRICS.evict(n))
+                .unwrap_or_else(|e| {
+                    warn!(
+                        "remove from list cache failed, {} {} {}",
+                        e, &self.key, p.id
+                    );
+                });
         } else {
             let (s, m) = self.p2pair(p);
             self.rconn.zadd(&self.key, m, s).await.unwrap_or_else(|e| {
@@ -256,20 +468,140 @@ impl PostListCache {
         }
     }
 
-    pub async fn get_pids(&mut self, start: i64, limit: i64) -> Vec<i32> {
+    // 超出缓存的范围时返回 None，需要查数据库
+    pub async fn get_pids(&mut self, start: i64, limit: i64) -> Option<Vec<i32>> {
+        let pids = if start + limit > self.i64_len() {
+            None
+        } else {
+            self.rconn
+                .zrange(
+                    &self.key,
+                    start.try_into().unwrap(),
+                    (start + limit - 1).try_into().unwrap(),
+                )
+                .await
+                .map_err(|e| warn!("get list cache failed, {}, {}", e, &self.key))
+                .ok()
+        };
+        POST_LIST_METRICS.record(&pids);
+        pids
+    }
+
+    // 游标翻页：返回 last 之后的 limit 条的 (排序值, pid)，顺序与数据库一致，即同一排序值内按 pid 从大到小
+    // 缓存中不够一页时返回 None，需要查数据库
+    pub async fn get_after(
+        &mut self,
+        last: Option<(i64, i32)>,
+        limit: usize,
+    ) -> Option<Vec<(i64, i32)>> {
+        let keys = if self.length < limit as isize {
+            None
+        } else {
+            self._get_after(last, limit)
+                .await
+                .map_err(|e| warn!("get list cache failed, {}, {}", e, &self.key))
+                .ok()
+                .filter(|keys| keys.len() >= limit)
+        };
+        POST_LIST_METRICS.record(&keys);
+        keys
+    }
+
+    // 同一排序值内 redis 按成员的字典序排列，与数据库不同，所以按排序值整组取出后再排序:
+    // 与 last 排序值相同的一组全部取出后过滤，被 LIMIT 截断的最后一组再单独补全
+    async fn _get_after(
+        &mut self,
+        last: Option<(i64, i32)>,
+        limit: usize,
+    ) -> RedisResult<Vec<(i64, i32)>> {
+        let (min, ties) = match last {
+            Some((k, _)) => (k.to_string(), self.rconn.zcount(&self.key, k, k).await?),
+            None => ("-inf".to_owned(), 0),
+        };
+        let count = limit + ties;
+        let items: Vec<(i32, f64)> = self
+            .rconn
+            .zrangebyscore_limit_withscores(&self.key, min, "+inf", 0, count as isize)
+            .await?;
+        let mut keys: Vec<(i64, i32)> = items
+            .into_iter()
+            .map(|(pid, score)| (score as i64, pid))
+            .collect();
+        if keys.len() >= count {
+            if let Some(&(k, _)) = keys.last() {
+                keys.retain(|&(key, _)| key != k);
+                let group: Vec<(i32, f64)> =
+                    self.rconn.zrangebyscore_withscores(&self.key, k, k).await?;
+                keys.extend(group.into_iter().map(|(pid, score)| (score as i64, pid)));
+            }
+        }
+        keys.retain(|&(k, pid)| last.map_or(true, |(lk, lpid)| k > lk || pid < lpid));
+        keys.sort_by_key(|&(k, pid)| (k, Reverse(pid)));
+        keys.truncate(limit);
+        Ok(keys)
+    }
+
+    pub async fn clear(&mut self) {
+        self.rconn
+            .del(&self.key)
+            .await
+            .map(|n| POST_LIST_METRICS.evict(n))
+            .unwrap_or_else(|e| {
+                warn!("clear post list cache failed, {}", e);
+            });
+    }
+}
+
+pub struct PinnedCache {
+    key: String,
+    rconn: RdsConn,
+}
+
+impl PinnedCache {
+    clear_all!("hole_v2:cache:pinned:*");
+
+    pub fn init(room_id: Option<i32>, rconn: &RdsConn) -> Self {
+        Self {
+            key: format!(
+                "hole_v2:cache:pinned:{}",
+                match room_id {
+                    Some(i) => i.to_string(),
+                    None => "".to_owned(),
+                }
+            ),
+            rconn: rconn.clone(),
+        }
+    }
+
+    pub async fn set(&mut self, pps: &[PinnedPost]) {
         self.rconn
-            .zrange(
+            .set_ex(
                 &self.key,
-                start.try_into().unwrap(),
-                (start + limit - 1).try_into().unwrap(),
+                serde_json::to_string(pps).unwrap(),
+                INSTANCE_EXPIRE_TIME,
             )
             .await
-            .unwrap()
+            .unwrap_or_else(|e| {
+                warn!("set pinned cache failed: {}", e);
+                dbg!(pps);
+            })
+    }
+
+    pub async fn get(&mut self) -> Option<Vec<PinnedPost>> {
+        let rds_result = self.rconn.get::<&String, String>(&self.key).await;
+        if let Ok(s) = rds_result {
+            serde_json::from_str(&s).unwrap_or_else(|e| {
+                warn!("get pinned cache, decode failed {}, {}", e, s);
+                None
+            })
+        } else {
+            None
+        }
     }
 
     pub async fn clear(&mut self) {
         self.rconn.del(&self.key).await.unwrap_or_else(|e| {
-            warn!("clear post list cache failed, {}", e);
+            warn!("clear pinned cache fail, {}", e);
         });
     }
 }
@@ -282,7 +614,7 @@ pub struct UserCache {
 impl UserCache {
     init!(&str, "hole_v2:cache:user:{}");
 
-    clear_all!("hole_v2:cache:user:*");
+    clear_all!("hole_v2:cache:user:*", USER_METRICS);
 
     pub async fn set(&mut self, u: &User) {
         self.rconn
@@ -292,6 +624,7 @@ impl UserCache {
                 INSTANCE_EXPIRE_TIME,
             )
             .await
+            .map(|()| USER_METRICS.fill(1))
             .unwrap_or_else(|e| {
                 warn!("set user cache failed: {}", e);
                 dbg!(u);
@@ -299,6 +632,12 @@ impl UserCache {
     }
 
     pub async fn get(&mut self) -> Option<User> {
+        let u = self._get().await;
+        USER_METRICS.record(&u);
+        u
+    }
+
+    async fn _get(&mut self) -> Option<User> {
         let rds_result = self.rconn.get::<&String, String>(&self.key).await;
         if let Ok(s) = rds_result {
             self.rconn
@@ -330,6 +669,8 @@ impl BlockDictCache {
     // namehash, pid
     init!(&str, i32, "hole_v2:cache:block_dict:{}:{}");
 
+    clear_all!("hole_v2:cache:block_dict:*", BLOCK_DICT_METRICS);
+
     pub async fn get_or_create(
         &mut self,
         user: &CurrentUser,
@@ -353,9 +694,12 @@ impl BlockDictCache {
             }))
             .await?;
 
+        BLOCK_DICT_METRICS.hit(hash_list.len() - missing.len());
+        BLOCK_DICT_METRICS.miss(missing.len());
         if !missing.is_empty() {
             self.rconn.hset_multiple(&self.key, &missing).await?;
             self.rconn.expire(&self.key, INSTANCE_EXPIRE_TIME).await?;
+            BLOCK_DICT_METRICS.fill(missing.len());
             block_dict.extend(missing.into_iter());
         }
 
@@ -365,7 +709,49 @@ impl BlockDictCache {
     }
 
     pub async fn clear(&mut self) -> RedisResult<()> {
-        self.rconn.del(&self.key).await
+        let n: usize = self.rconn.del(&self.key).await?;
+        BLOCK_DICT_METRICS.evict(n);
+        Ok(())
+    }
+}
+
+pub struct RoomCache {
+    rconn: RdsConn,
+}
+
+impl RoomCache {
+    init!();
+
+    pub async fn set(&mut self, rooms: &[Room]) {
+        self.rconn
+            .set_ex(
+                KEY_ROOMS,
+                serde_json::to_string(rooms).unwrap(),
+                INSTANCE_EXPIRE_TIME,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                warn!("set rooms cache failed: {}", e);
+                dbg!(rooms);
+            })
+    }
+
+    pub async fn get(&mut self) -> Option<Vec<Room>> {
+        let rds_result = self.rconn.get::<&str, String>(KEY_ROOMS).await;
+        if let Ok(s) = rds_result {
+            serde_json::from_str(&s).unwrap_or_else(|e| {
+                warn!("get rooms cache, decode failed {}, {}", e, s);
+                None
+            })
+        } else {
+            None
+        }
+    }
+
+    pub async fn clear(&mut self) {
+        self.rconn.del(KEY_ROOMS).await.unwrap_or_else(|e| {
+            warn!("clear rooms cache fail, {}", e);
+        });
     }
 }
 
@@ -381,3 +767,72 @@ pub async fn cached_user_count(db: &Db, rconn: &mut RdsConn) -> Api<i64> {
         Ok(x)
     }
 }
+
+// 查看某个洞相关的缓存，不刷新过期时间也不计入统计
+pub async fn inspect_post_cache(rconn: &RdsConn, pid: i32) -> RedisResult<Value> {
+    let mut rconn = rconn.clone();
+    let mut entries = serde_json::Map::new();
+    for (name, key) in [
+        ("post", post_cache_key!(pid)),
+        ("comments", format!("hole_v2:cache:post_comments:{}", pid)),
+        ("poll", format!("hole_v2:cache:poll:{}", pid)),
+        (
+            "emoji_reactions",
+            format!("hole_v2:cache:emoji_reactions:{}", pid),
+        ),
+    ] {
+        let v: Option<String> = rconn.get(&key).await?;
+        let ttl: i64 = rconn.ttl(&key).await?;
+        entries.insert(
+            name.to_string(),
+            json!({
+                "key": key,
+                "ttl": ttl,
+                "value": v.map(|s| serde_json::from_str(&s).unwrap_or(Value::String(s))),
+            }),
+        );
+    }
+
+    let list_keys: Vec<String> = rconn
+        .scan_match::<&str, String>("hole_v2:cache:post_list:*")
+        .await?
+        .collect::<Vec<String>>()
+        .await;
+    let mut lists = vec![];
+    for key in list_keys {
+        let score: Option<i64> = rconn.zscore(&key, pid).await?;
+        if let Some(score) = score {
+            lists.push(json!({ "key": key, "score": score }));
+        }
+    }
+    entries.insert("lists".to_string(), Value::Array(lists));
+
+    Ok(Value::Object(entries))
+}
+
+pub const CACHE_FAMILIES: [&str; 8] = [
+    "post",
+    "post_comment",
+    "post_list",
+    "user",
+    "block_dict",
+    "poll",
+    "emoji_reaction",
+    "pinned",
+];
+
+// return None if family not found
+pub async fn flush_cache_family(rconn: &RdsConn, family: &str) -> Option<usize> {
+    let mut rconn = rconn.clone();
+    Some(match family {
+        "post" => PostCache::clear_all(&mut rconn).await,
+        "post_comment" => PostCommentCache::clear_all(&mut rconn).await,
+        "post_list" => PostListCache::clear_all(&mut rconn).await,
+        "user" => UserCache::clear_all(&mut rconn).await,
+        "block_dict" => BlockDictCache::clear_all(&mut rconn).await,
+        "poll" => PollCache::clear_all(&mut rconn).await,
+        "emoji_reaction" => EmojiReactionCache::clear_all(&mut rconn).await,
+        "pinned" => PinnedCache::clear_all(&mut rconn).await,
+        _ => return None,
+    })
+}
@@ -0,0 +1,193 @@
+// 运维用的子命令，复用模型层的函数，不需要手写 SQL 或 redis 命令
+use crate::cache::{flush_cache_family, UserCache, CACHE_FAMILIES};
+use crate::config::{AppConfig, StoreBackend};
+use crate::db_conn::{establish_connection, Conn};
+use crate::models::{Post, User};
+use crate::random_hasher::random_string;
+use crate::rds_conn::RdsConn;
+use crate::rds_models::*;
+use chrono::offset::Local;
+use std::process;
+
+const USAGE: &str = "用法: hole-thu [子命令]
+
+不带子命令时启动服务。
+
+子命令:
+    --init-database             运行数据库迁移
+    create-admin <name>         创建管理员(已存在则设为管理员)，输出 token
+    ban <namehash> [理由]       封禁用户，重启服务或轮换 salt 后失效
+    set-announcement [公告]     设置公告，不带参数时清除
+    list-reports                列出被举报隐藏的洞和最近的举报
+    reindex-tags                重建 tag 搜索使用的索引
+    rotate-salt                 轮换 salt，非管理员用户需要重新登录
+    flush-cache [family|all]    清除缓存，默认全部
+    migrate-sqlite <path> [--batch-size <n>]
+                                从旧版 SQLite 数据库迁移，需要 migdb feature
+    help                        显示此帮助";
+
+type CliResult = Result<(), String>;
+
+#[cfg(feature = "migdb")]
+const DEFAULT_MIGRATE_BATCH: i64 = 1000;
+// postgres 单条语句最多 65535 个参数
+#[cfg(feature = "migdb")]
+const MAX_MIGRATE_BATCH: i64 = 4000;
+
+// 返回 false 表示没有子命令，应当启动服务
+pub async fn run(args: &[String], config: &AppConfig) -> bool {
+    let (cmd, args) = match args.split_first() {
+        Some((cmd, args)) => (cmd.as_str(), args),
+        None => return false,
+    };
+    if matches!(cmd, "help" | "--help" | "-h") {
+        println!("{}", USAGE);
+        return true;
+    }
+
+    let mut c = establish_connection(&config.database_url);
+    if config.store == StoreBackend::Memory {
+        eprintln!("警告: store 为 memory 时命令行工具无法修改运行中的服务的缓存和封禁等数据");
+    }
+    let mut rconn = RdsConn::connect(config).await;
+
+    let result = match (cmd, args) {
+        ("create-admin", [name]) => create_admin(&mut c, &mut rconn, name).await,
+        ("ban", [namehash, reason @ ..]) => ban(&rconn, namehash, &reason.join(" ")).await,
+        ("set-announcement", text) => announce(&rconn, &text.join(" ")).await,
+        ("list-reports", []) => list_reports(&mut c, &rconn).await,
+        ("reindex-tags", []) => reindex_tags(&mut c),
+        ("rotate-salt", []) => rotate_salt(&mut c, &mut rconn).await,
+        ("flush-cache", []) => flush_cache(&rconn, "all").await,
+        ("flush-cache", [family]) => flush_cache(&rconn, family).await,
+        #[cfg(feature = "migdb")]
+        ("migrate-sqlite", [path]) => migrate_sqlite(&c, path, DEFAULT_MIGRATE_BATCH),
+        #[cfg(feature = "migdb")]
+        ("migrate-sqlite", [path, flag, n]) if flag == "--batch-size" => match n.parse() {
+            Ok(n) if (1..=MAX_MIGRATE_BATCH).contains(&n) => migrate_sqlite(&c, path, n),
+            _ => Err(format!("batch-size 应在 1 到 {} 之间", MAX_MIGRATE_BATCH)),
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("错误: {}", e);
+        process::exit(1);
+    }
+    true
+}
+
+async fn create_admin(c: &mut Conn, rconn: &mut RdsConn, name: &str) -> CliResult {
+    let token = User::set_admin_sync(c, name).map_err(|e| e.to_string())?;
+    // 缓存中可能还是非管理员
+    UserCache::clear_all(rconn).await;
+    println!("管理员 {} 的 token: {}", name, token);
+    Ok(())
+}
+
+async fn ban(rconn: &RdsConn, namehash: &str, reason: &str) -> CliResult {
+    BannedUsers::add(rconn, namehash)
+        .await
+        .map_err(|e| e.to_string())?;
+    Systemlog {
+        user_hash: "命令行".to_string(),
+        action_type: LogType::Ban,
+        target: namehash.to_string(),
+        detail: reason.to_string(),
+        time: Local::now(),
+    }
+    .create(rconn)
+    .await
+    .map_err(|e| e.to_string())?;
+    println!("已封禁 {}", namehash);
+    Ok(())
+}
+
+async fn announce(rconn: &RdsConn, text: &str) -> CliResult {
+    set_announcement(rconn, text)
+        .await
+        .map_err(|e| e.to_string())?;
+    if text.is_empty() {
+        println!("已清除公告");
+    } else {
+        println!("已设置公告");
+    }
+    Ok(())
+}
+
+async fn list_reports(c: &mut Conn, rconn: &RdsConn) -> CliResult {
+    let ps = Post::get_reported_sync(c).map_err(|e| e.to_string())?;
+    println!("被举报隐藏的洞 ({}):", ps.len());
+    for p in ps.iter() {
+        println!(
+            "  #{}\t{}\t{}",
+            p.id,
+            p.create_time.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            p.content
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .take(40)
+                .collect::<String>(),
+        );
+    }
+
+    let logs = Systemlog::get_list(rconn, -1)
+        .await
+        .map_err(|e| e.to_string())?;
+    println!("最近的举报:");
+    for log in logs
+        .iter()
+        .filter(|log| matches!(log.action_type, LogType::Report))
+    {
+        println!(
+            "  {}\t{}\t{}\t{}",
+            log.time.format("%Y-%m-%d %H:%M"),
+            log.target,
+            log.user_hash,
+            log.detail,
+        );
+    }
+    Ok(())
+}
+
+fn reindex_tags(c: &mut Conn) -> CliResult {
+    Post::reindex_tags_sync(c).map_err(|e| e.to_string())?;
+    println!("索引已重建");
+    Ok(())
+}
+
+// 与重启服务的效果相同，运行中的服务会在几秒内换用新的 salt
+async fn rotate_salt(c: &mut Conn, rconn: &mut RdsConn) -> CliResult {
+    set_salt(rconn, &random_string(16))
+        .await
+        .map_err(|e| e.to_string())?;
+    User::clear_non_admin_users(c, rconn).await;
+    clear_outdate_redis_data(rconn).await;
+    println!("salt 已轮换，非管理员用户需要重新登录");
+    Ok(())
+}
+
+async fn flush_cache(rconn: &RdsConn, family: &str) -> CliResult {
+    let families: Vec<&str> = if family == "all" {
+        CACHE_FAMILIES.to_vec()
+    } else {
+        vec![family]
+    };
+    for f in families {
+        let n = flush_cache_family(rconn, f)
+            .await
+            .ok_or_else(|| format!("未知的缓存 {}，可选: all, {}", f, CACHE_FAMILIES.join(", ")))?;
+        println!("{}: 清除了 {} 个 key", f, n);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "migdb")]
+fn migrate_sqlite(c: &Conn, path: &str, batch: i64) -> CliResult {
+    crate::migdb::run(path, c, batch)
+}
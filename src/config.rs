@@ -0,0 +1,372 @@
+// 所有配置项在启动时统一读取并校验，之后作为 rocket 的 state 使用
+// 读取顺序(后者覆盖前者): Rocket.toml, Hole.toml (可用 HOLE_CONFIG 指定路径), 环境变量/.env
+// 环境变量名为配置项的大写形式，定时任务为 JOB_<NAME>
+use crate::jobs;
+use rocket::data::ByteUnit;
+use rocket::figment::providers::{Env, Format, Toml};
+use rocket::figment::Figment;
+use rocket::serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use url::Url;
+
+// 可以从环境变量中读取的配置项
+const ENV_KEYS: [&str; 28] = [
+    "database_url",
+    "store",
+    "redis_url",
+    "frontend_whitelist",
+    "default_frontend",
+    "default_backend",
+    "mast_base_url",
+    "mast_client",
+    "mast_secret",
+    "mast_scope",
+    "gh_client",
+    "gh_secret",
+    "upload_dir",
+    "upload_limit",
+    "push_private_key",
+    "emoji_set",
+    "max_pinned_posts",
+    "author_can_lock",
+    "hot_decay_half_life",
+    "hot_weight_attention",
+    "hot_weight_comment",
+    "hot_weight_reaction",
+    "metrics_token",
+    "metrics_allow_ips",
+    "slow_query_info_ms",
+    "slow_query_warn_ms",
+    "slow_query_top_n",
+    "query_log_json",
+];
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RoomSeed {
+    pub id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+// memory 只适合单实例部署，数据在重启后丢失，命令行工具也无法操作运行中的服务的数据
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum StoreBackend {
+    #[default]
+    Redis,
+    Memory,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AppConfig {
+    pub database_url: String,
+    #[serde(default)]
+    pub store: StoreBackend,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub redis_url: Option<String>,
+
+    // 登录
+    #[serde(default, deserialize_with = "comma_list")]
+    pub frontend_whitelist: Vec<String>,
+    pub default_frontend: Option<String>,
+    pub default_backend: Option<String>,
+    pub mast_base_url: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub mast_client: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub mast_secret: Option<String>,
+    #[serde(default = "default_mast_scope")]
+    pub mast_scope: String,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub gh_client: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub gh_secret: Option<String>,
+
+    // 上传与推送
+    #[serde(default = "default_upload_dir")]
+    pub upload_dir: String,
+    #[serde(default = "default_upload_limit")]
+    pub upload_limit: ByteUnit,
+    #[serde(default = "default_push_private_key")]
+    pub push_private_key: String,
+
+    // 启动时创建不存在的房间，已有的房间不会被修改
+    #[serde(default)]
+    pub rooms: Vec<RoomSeed>,
+    #[serde(default = "default_emoji_set", deserialize_with = "comma_list")]
+    pub emoji_set: Vec<String>,
+    #[serde(default = "default_max_pinned_posts")]
+    pub max_pinned_posts: i64,
+    #[serde(default)]
+    pub author_can_lock: bool,
+
+    // order_mode=5 的时间衰减热度，半衰期单位为小时
+    #[serde(default = "default_hot_decay_half_life")]
+    pub hot_decay_half_life: f64,
+    #[serde(default = "default_weight")]
+    pub hot_weight_attention: f64,
+    #[serde(default = "default_weight")]
+    pub hot_weight_comment: f64,
+    #[serde(default = "default_weight")]
+    pub hot_weight_reaction: f64,
+
+    // 任务名 -> 调度方式，未设置的使用默认值
+    #[serde(default)]
+    pub jobs: BTreeMap<String, String>,
+
+    #[serde(default, deserialize_with = "opt_string")]
+    pub metrics_token: Option<String>,
+    #[serde(default = "default_metrics_allow_ips", deserialize_with = "comma_list")]
+    pub metrics_allow_ips: Vec<IpAddr>,
+
+    #[serde(default = "default_slow_query_info_ms")]
+    pub slow_query_info_ms: u64,
+    #[serde(default = "default_slow_query_warn_ms")]
+    pub slow_query_warn_ms: u64,
+    #[serde(default = "default_slow_query_top_n")]
+    pub slow_query_top_n: usize,
+    #[serde(default)]
+    pub query_log_json: bool,
+}
+
+fn default_mast_scope() -> String {
+    "read:accounts".to_string()
+}
+
+fn default_upload_dir() -> String {
+    "user_files".to_string()
+}
+
+fn default_upload_limit() -> ByteUnit {
+    ByteUnit::Megabyte(200)
+}
+
+fn default_push_private_key() -> String {
+    "keys/private.pem".to_string()
+}
+
+fn default_emoji_set() -> Vec<String> {
+    ["👍", "😂", "😢", "🤔"].map(String::from).to_vec()
+}
+
+fn default_max_pinned_posts() -> i64 {
+    3
+}
+
+fn default_hot_decay_half_life() -> f64 {
+    24.0
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_metrics_allow_ips() -> Vec<IpAddr> {
+    vec![[127, 0, 0, 1].into(), [0, 0, 0, 0, 0, 0, 0, 1].into()]
+}
+
+fn default_slow_query_info_ms() -> u64 {
+    1000
+}
+
+fn default_slow_query_warn_ms() -> u64 {
+    5000
+}
+
+fn default_slow_query_top_n() -> usize {
+    20
+}
+
+// 环境变量中的值会被解析成数字或布尔值，id 和密钥需要当作字符串处理
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+enum Scalar {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+fn opt_string<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    let s = match Scalar::deserialize(d)? {
+        Scalar::Str(s) => s,
+        Scalar::Int(n) => n.to_string(),
+        Scalar::Float(f) => f.to_string(),
+        Scalar::Bool(b) => b.to_string(),
+    };
+    Ok((!s.trim().is_empty()).then_some(s))
+}
+
+// 支持 TOML 中的数组和环境变量中逗号分隔的字符串
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+enum CommaList {
+    List(Vec<String>),
+    Str(String),
+}
+
+fn comma_list<'de, D, T>(d: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let items = match CommaList::deserialize(d)? {
+        CommaList::List(v) => v,
+        CommaList::Str(s) => s.split(',').map(String::from).collect(),
+    };
+    items
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .map_err(|e| rocket::serde::de::Error::custom(format!("{}: {}", s, e)))
+        })
+        .collect()
+}
+
+fn figment() -> Figment {
+    let path = env::var("HOLE_CONFIG").unwrap_or_else(|_| "Hole.toml".to_string());
+    rocket::Config::figment().merge(Toml::file(path)).merge(
+        Env::raw()
+            .filter(|k| {
+                let k = k.as_str().to_ascii_lowercase();
+                ENV_KEYS.contains(&k.as_str()) || k.starts_with("job_")
+            })
+            .map(|k| {
+                let k = k.as_str().to_ascii_lowercase();
+                match k.strip_prefix("job_") {
+                    Some(name) => format!("jobs.{}", name).into(),
+                    None => k.into(),
+                }
+            }),
+    )
+}
+
+impl AppConfig {
+    // 配置有误时打印所有错误并退出，不带着错误的配置启动
+    pub fn load() -> (Self, Figment) {
+        Self::from_figment(figment()).unwrap_or_else(|errors| {
+            for e in errors {
+                eprintln!("Configuration error: {}", e);
+            }
+            process::exit(1);
+        })
+    }
+
+    pub fn from_figment(figment: Figment) -> Result<(Self, Figment), Vec<String>> {
+        let config: Self = figment
+            .extract()
+            .map_err(|e| e.into_iter().map(|e| e.to_string()).collect::<Vec<_>>())?;
+        config.validate()?;
+
+        // 数据库连接池和上传大小限制也由这里的配置决定，ROCKET_DATABASES 等仍可覆盖
+        let figment = figment
+            .merge(("limits.file", config.upload_limit))
+            .join(("databases.pg_v2.url", config.database_url.clone()));
+        Ok((config, figment))
+    }
+
+    // 初始化数据库只需要 database_url，不要求其它配置完整有效
+    pub fn load_database_url() -> String {
+        figment().extract_inner("database_url").unwrap_or_else(|e| {
+            eprintln!("Configuration error: {}", e);
+            process::exit(1);
+        })
+    }
+
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+
+        if self.store == StoreBackend::Redis && self.redis_url.is_none() {
+            errors.push("redis_url is required when store is redis".to_string());
+        }
+
+        for url in self.frontend_whitelist.iter() {
+            if let Err(e) = Url::parse(url) {
+                errors.push(format!("frontend_whitelist: invalid url {}: {}", url, e));
+            }
+        }
+
+        let mast = [&self.mast_base_url, &self.mast_client, &self.mast_secret];
+        if mast.iter().any(|v| v.is_some()) && !mast.iter().all(|v| v.is_some()) {
+            errors.push(
+                "mast_base_url, mast_client and mast_secret must be set together".to_string(),
+            );
+        }
+        if let Some(url) = &self.mast_base_url {
+            if Url::parse(url).is_err() || !url.ends_with('/') {
+                errors.push(format!(
+                    "mast_base_url: expect an url ending with '/', got {}",
+                    url
+                ));
+            }
+        }
+        if self.gh_client.is_some() != self.gh_secret.is_some() {
+            errors.push("gh_client and gh_secret must be set together".to_string());
+        }
+
+        // 目录在启动时才创建，这里只检查路径没有被文件占用
+        if Path::new(&self.upload_dir).exists() && !Path::new(&self.upload_dir).is_dir() {
+            errors.push(format!(
+                "upload_dir: {} is not a directory",
+                self.upload_dir
+            ));
+        }
+
+        let mut room_ids: Vec<i32> = self.rooms.iter().map(|r| r.id).collect();
+        room_ids.sort_unstable();
+        room_ids.dedup();
+        if room_ids.len() != self.rooms.len() {
+            errors.push("rooms: duplicate room id".to_string());
+        }
+        if self.rooms.iter().any(|r| r.name.trim().is_empty()) {
+            errors.push("rooms: room name can not be empty".to_string());
+        }
+
+        if self.emoji_set.is_empty() {
+            errors.push("emoji_set can not be empty".to_string());
+        }
+        if self.max_pinned_posts < 0 {
+            errors.push("max_pinned_posts can not be negative".to_string());
+        }
+
+        if !(self.hot_decay_half_life > 0.0 && self.hot_decay_half_life.is_finite()) {
+            errors.push("hot_decay_half_life must be positive".to_string());
+        }
+        if ![
+            self.hot_weight_attention,
+            self.hot_weight_comment,
+            self.hot_weight_reaction,
+        ]
+        .iter()
+        .all(|w| w.is_finite())
+        {
+            errors.push("hot_weight_* must be finite numbers".to_string());
+        }
+
+        if let Err(e) = jobs::check_config(&self.jobs) {
+            errors.push(e);
+        }
+
+        if self.slow_query_info_ms > self.slow_query_warn_ms {
+            errors.push("slow_query_info_ms can not exceed slow_query_warn_ms".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
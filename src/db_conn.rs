@@ -1,15 +1,83 @@
-use diesel::Connection;
+use crate::libs::diesel_logger::with_route;
+use diesel::{Connection, QueryResult};
+use rocket::outcome::try_outcome;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::tokio;
 use rocket_sync_db_pools::{database, diesel};
-use std::env;
 
 pub type Conn = diesel::pg::PgConnection;
 
 #[database("pg_v2")]
-pub struct Db(Conn);
+pub struct DbPool(Conn);
 
-// get sync connection, only for annealing
-pub fn establish_connection() -> Conn {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    Conn::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+// 记录当前的路由，慢查询日志中可以看到是哪个接口发出的
+pub struct Db {
+    pool: DbPool,
+    route: Option<String>,
+}
+
+impl Db {
+    pub async fn run<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Conn) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let route = self.route.clone();
+        self.pool.run(move |c| with_route(route, || f(c))).await
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Db {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let pool = try_outcome!(request.guard::<DbPool>().await);
+        Outcome::Success(Db {
+            pool,
+            route: request
+                .route()
+                .map(|r| format!("{} {}", r.method, r.uri.path())),
+        })
+    }
+}
+
+// get sync connection, only for startup and background jobs
+pub fn establish_connection(database_url: &str) -> Conn {
+    Conn::establish(database_url).unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+}
+
+// 后台任务使用的连接，查询在阻塞线程池中运行，不占用异步 worker
+pub struct JobConn {
+    database_url: String,
+    conn: Option<Conn>,
+}
+
+impl JobConn {
+    pub fn new(database_url: &str) -> Self {
+        Self {
+            database_url: database_url.to_string(),
+            conn: None,
+        }
+    }
+
+    // 没有连接时先建立连接，出错时丢弃连接，下次重新建立
+    pub async fn run<F, R>(&mut self, f: F) -> QueryResult<R>
+    where
+        F: FnOnce(&mut Conn) -> QueryResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let c = self.conn.take();
+        let database_url = self.database_url.clone();
+        let (c, result) = tokio::task::spawn_blocking(move || {
+            let mut c = c.unwrap_or_else(|| establish_connection(&database_url));
+            let result = f(&mut c);
+            (c, result)
+        })
+        .await
+        .expect("job query panicked");
+        if result.is_ok() {
+            self.conn = Some(c);
+        }
+        result
+    }
 }
@@ -0,0 +1,113 @@
+// 供 docker-compose 等部署工具检查服务状态
+use crate::config::AppConfig;
+use crate::db_conn::Db;
+use crate::random_hasher::RandomHasher;
+use crate::rds_conn::RdsConn;
+use diesel::RunQueryDsl;
+use diesel_migrations::MigrationConnection;
+use rocket::http::Status;
+use rocket::serde::json::{json, serde_json, Value};
+use rocket::tokio::fs;
+use rocket::State;
+use std::future::Future;
+use std::path::Path;
+use std::time::Instant;
+
+include!(concat!(env!("OUT_DIR"), "/migration_versions.rs"));
+
+#[get("/_health")]
+pub async fn health(rh: &State<RandomHasher>) -> Value {
+    json!({
+        "status": "ok",
+        "start_time": rh.start_time.timestamp(),
+    })
+}
+
+async fn check<F>(f: F) -> (bool, Value)
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let r = f.await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    (
+        r.is_ok(),
+        json!({
+            "ok": r.is_ok(),
+            "latency_ms": latency_ms,
+            "error": r.err(),
+        }),
+    )
+}
+
+async fn check_postgres(db: &Option<Db>) -> Result<(), String> {
+    db.as_ref()
+        .ok_or("no connection available")?
+        .run(|c| diesel::sql_query("SELECT 1").execute(c))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_migrations(db: &Option<Db>) -> Result<(), String> {
+    let applied = db
+        .as_ref()
+        .ok_or("no connection available")?
+        .run(|c| c.previously_run_migration_versions())
+        .await
+        .map_err(|e| e.to_string())?;
+    let pending: Vec<&str> = MIGRATION_VERSIONS
+        .iter()
+        .filter(|v| !applied.contains(**v))
+        .copied()
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("pending migrations: {}", pending.join(", ")))
+    }
+}
+
+async fn check_redis(rconn: &RdsConn) -> Result<(), String> {
+    let mut rconn = rconn.clone();
+    redis::cmd("PING")
+        .query_async::<_, String>(&mut rconn)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_upload_dir(dir: &str) -> Result<(), String> {
+    let path = Path::new(dir).join(".ready_check");
+    fs::write(&path, b"ok").await.map_err(|e| e.to_string())?;
+    fs::remove_file(&path).await.map_err(|e| e.to_string())
+}
+
+#[get("/_ready")]
+pub async fn ready(db: Option<Db>, rconn: RdsConn, config: &State<AppConfig>) -> (Status, Value) {
+    let checks = [
+        ("postgres", check(check_postgres(&db)).await),
+        ("migrations", check(check_migrations(&db)).await),
+        ("redis", check(check_redis(&rconn)).await),
+        (
+            "upload_dir",
+            check(check_upload_dir(&config.upload_dir)).await,
+        ),
+    ];
+    let all_ok = checks.iter().all(|(_, (ok, _))| *ok);
+
+    (
+        if all_ok {
+            Status::Ok
+        } else {
+            Status::ServiceUnavailable
+        },
+        json!({
+            "status": if all_ok { "ok" } else { "error" },
+            "checks": checks
+                .into_iter()
+                .map(|(name, (_, detail))| (name.to_string(), detail))
+                .collect::<serde_json::Map<String, Value>>(),
+        }),
+    )
+}
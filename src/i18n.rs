@@ -0,0 +1,172 @@
+// 面向用户的提示文本，目前支持简体中文和英文
+// 优先使用用户在 /lang 中的设置，否则按 Accept-Language 选择，都没有时使用中文
+use crate::api::PolicyError;
+use rocket::request::{FromRequest, Outcome, Request};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    ZhCn,
+    En,
+}
+
+// 由 CurrentUser 的请求守卫写入已登录用户的语言，之后的错误提示和 Lang 守卫都会读到
+pub struct UserLang(pub Option<Lang>);
+
+impl Lang {
+    // 只看主标签，zh-TW 等也按 zh-CN 处理
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "zh" => Some(Lang::ZhCn),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Lang::ZhCn => "zh-CN",
+            Lang::En => "en",
+        }
+    }
+
+    // 取权重最高的已支持语言，如 "en-US,en;q=0.9,zh;q=0.8"
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        header
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let lang = Lang::parse(parts.next()?)?;
+                let q = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (q > 0.0).then_some((lang, q))
+            })
+            .fold(None, |best: Option<(Lang, f32)>, (lang, q)| match best {
+                Some((_, bq)) if bq >= q => best,
+                _ => Some((lang, q)),
+            })
+            .map(|(lang, _)| lang)
+    }
+
+    pub fn from_headers(request: &Request<'_>) -> Self {
+        request
+            .headers()
+            .get_one("Accept-Language")
+            .and_then(Lang::from_accept_language)
+            .unwrap_or_default()
+    }
+
+    pub fn of(request: &Request<'_>) -> Self {
+        request
+            .local_cache(|| UserLang(None))
+            .0
+            .unwrap_or_else(|| Lang::from_headers(request))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Lang {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Lang::of(request))
+    }
+}
+
+#[derive(Debug)]
+pub enum Msg<'a> {
+    NotLoggedIn,
+    Banned,
+    Unavailable,
+    UpdateFrontend,
+    InvalidForm,
+    Policy(&'a PolicyError),
+    OwnerDeleted,
+    ReportCw,
+    MastNotConfigured,
+    GhNotConfigured,
+    FrontendNotWhitelisted,
+    NoThuEmail,
+}
+
+impl Msg<'_> {
+    pub fn text(&self, lang: Lang) -> &'static str {
+        let (zh, en) = match self {
+            Msg::NotLoggedIn => ("未登录或token过期", "Not logged in or token expired"),
+            Msg::Banned => (
+                "可能被封禁了，等下次重置吧",
+                "You may have been banned until the next reset",
+            ),
+            Msg::Unavailable => (
+                "服务暂时不可用，请稍后再试",
+                "Service temporarily unavailable, please try again later",
+            ),
+            Msg::UpdateFrontend => ("请更新前端版本", "Please update the frontend"),
+            Msg::InvalidForm => ("参数不正确", "Invalid parameters"),
+            Msg::Policy(e) => match e {
+                PolicyError::IsReported => ("内容被举报，处理中", "Reported and under review"),
+                PolicyError::IsPrivate => ("未被设置为公开", "Not public"),
+                PolicyError::IsDeleted => ("内容被删除", "Deleted"),
+                PolicyError::NotAllowed => ("不允许的操作", "Operation not allowed"),
+                PolicyError::TitleUsed => ("头衔已被使用", "Title already in use"),
+                PolicyError::TitleProtected => ("头衔处于保护期", "Title is protected for now"),
+                PolicyError::InvalidTitle => (
+                    "头衔包含不允许的符号",
+                    "Title contains disallowed characters",
+                ),
+                PolicyError::YouAreTmp => {
+                    ("临时用户只可发布内容", "Temporary users can only publish")
+                }
+                PolicyError::NoReason => ("未填写理由", "Reason is required"),
+                PolicyError::InvalidFormat => ("不支持的导出格式", "Unsupported export format"),
+                PolicyError::UnknownJob => ("没有这个定时任务", "No such job"),
+                PolicyError::UnknownCacheFamily => ("没有这类缓存", "No such cache family"),
+                PolicyError::UnknownPushEndpoint => {
+                    ("未知的浏览器推送地址", "Unknown push endpoint")
+                }
+                PolicyError::UnknownEmoji => ("不支持的表情", "Unsupported emoji"),
+                PolicyError::TooManyPinned => ("置顶数量已达上限", "Too many pinned posts"),
+                PolicyError::IsLocked => ("已锁定，不能评论", "Locked, comments are closed"),
+                PolicyError::InvalidExpireTime => (
+                    "自毁时间需在10分钟到30天之间",
+                    "Expire time must be between 10 minutes and 30 days",
+                ),
+                PolicyError::IsScheduled => ("尚未发布", "Not published yet"),
+                PolicyError::InvalidPublishTime => (
+                    "定时发布的时间需在未来30天内",
+                    "Scheduled time must be within the next 30 days",
+                ),
+                PolicyError::TooManyDrafts => ("草稿数量已达上限", "Too many drafts"),
+                PolicyError::UnknownLang => ("不支持的语言", "Unsupported language"),
+                PolicyError::InvalidCursor => {
+                    ("列表已过期，请刷新", "The list is outdated, please refresh")
+                }
+            },
+            Msg::OwnerDeleted => ("[洞主已删除]", "[Deleted by the author]"),
+            Msg::ReportCw => ("举报", "Report"),
+            Msg::MastNotConfigured => ("未配置闭社登录", "Closed.social login is not configured"),
+            Msg::GhNotConfigured => ("未配置GitHub登录", "GitHub login is not configured"),
+            Msg::FrontendNotWhitelisted => (
+                "前端地址不在白名单内",
+                "Frontend address is not whitelisted",
+            ),
+            Msg::NoThuEmail => (
+                "没有找到已验证的清华邮箱",
+                "No verified Tsinghua email found",
+            ),
+        };
+        match lang {
+            Lang::ZhCn => zh,
+            Lang::En => en,
+        }
+    }
+}
+
+// 举报时自动发到举报房间的内容
+pub fn report_post(lang: Lang, pid: i32, reason: &str) -> String {
+    match lang {
+        Lang::ZhCn => format!("[系统自动代发]\n我举报了 #{}\n理由: {}", pid, reason),
+        Lang::En => format!("[Auto-posted]\nI reported #{}\nReason: {}", pid, reason),
+    }
+}
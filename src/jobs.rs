@@ -0,0 +1,290 @@
+// 定时任务。调度方式可在配置项 jobs 或环境变量 JOB_<NAME> 中配置:
+//   "every 30s" / "every 5m" / "every 3h" / "every 1d"
+//   "daily 04:30" (本地时间)
+//   "off"
+use crate::cache::PostListCache;
+use crate::config::AppConfig;
+use crate::db_conn::JobConn;
+use crate::metrics::JOB_RUNS;
+use crate::models::{Post, Room};
+use crate::rds_conn::RdsConn;
+use chrono::{offset::Local, NaiveTime};
+use diesel::QueryResult;
+use rocket::futures::future::BoxFuture;
+use rocket::serde::Serialize;
+use rocket::tokio::{
+    self,
+    sync::Notify,
+    time::{sleep, Duration, Instant},
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// 数据库出错时返回 Err，由调度器记录日志和状态
+type JobFn = for<'a> fn(&'a mut JobConn, &'a mut RdsConn) -> BoxFuture<'a, QueryResult<()>>;
+
+// name, 默认调度, 启动时是否立即执行一次, 任务
+// annealing 默认关闭，order_mode=5 的时间衰减热度不需要定时降温
+const JOBS: [(&str, &str, bool, JobFn); 4] = [
+    ("annealing", "off", false, annealing),
+    ("refresh_random", "every 5m", true, refresh_random),
+    ("publish_scheduled", "every 1m", true, publish_scheduled),
+    ("clear_expired", "every 1m", true, clear_expired),
+];
+
+fn annealing<'a>(c: &'a mut JobConn, rconn: &'a mut RdsConn) -> BoxFuture<'a, QueryResult<()>> {
+    Box::pin(Post::annealing(c, rconn))
+}
+
+fn refresh_random<'a>(
+    c: &'a mut JobConn,
+    rconn: &'a mut RdsConn,
+) -> BoxFuture<'a, QueryResult<()>> {
+    Box::pin(async move {
+        let room_ids = c
+            .run(Room::all_sync)
+            .await?
+            .into_iter()
+            .map(|r| Some(r.id))
+            .chain([None]);
+        for room_id in room_ids {
+            PostListCache::init(room_id, 3, rconn).clear().await;
+        }
+        Ok(())
+    })
+}
+
+fn publish_scheduled<'a>(
+    c: &'a mut JobConn,
+    rconn: &'a mut RdsConn,
+) -> BoxFuture<'a, QueryResult<()>> {
+    Box::pin(Post::publish_scheduled(c, rconn))
+}
+
+fn clear_expired<'a>(c: &'a mut JobConn, rconn: &'a mut RdsConn) -> BoxFuture<'a, QueryResult<()>> {
+    Box::pin(Post::clear_expired(c, rconn))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Schedule {
+    Every(Duration),
+    Daily(NaiveTime),
+    Off,
+}
+
+impl Schedule {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s == "off" {
+            return Some(Self::Off);
+        }
+        if let Some(t) = s.strip_prefix("daily ") {
+            return NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .ok()
+                .map(Self::Daily);
+        }
+        let s = s.strip_prefix("every ")?.trim();
+        let unit = s.chars().last()?;
+        let n: u64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+        let secs = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return None,
+        };
+        (n > 0).then(|| Self::Every(Duration::from_secs(n * secs)))
+    }
+
+    // None: 只能手动触发
+    fn next_delay(&self) -> Option<Duration> {
+        match self {
+            Self::Every(d) => Some(*d),
+            Self::Daily(t) => {
+                let now = Local::now().naive_local();
+                let mut target = now.date().and_time(*t);
+                if target <= now {
+                    target += chrono::Duration::days(1);
+                }
+                (target - now).to_std().ok()
+            }
+            Self::Off => None,
+        }
+    }
+}
+
+// 启动前检查配置，任务名和调度方式都需要有效
+pub fn check_config(schedules: &BTreeMap<String, String>) -> Result<(), String> {
+    for (name, s) in schedules {
+        if !JOBS.iter().any(|(n, ..)| *n == name.as_str()) {
+            return Err(format!("jobs: unknown job {}", name));
+        }
+        Schedule::parse(s).ok_or_else(|| format!("jobs: invalid schedule {}={}", name, s))?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Every(d) => write!(f, "every {}s", d.as_secs()),
+            Self::Daily(t) => write!(f, "daily {}", t.format("%H:%M")),
+            Self::Off => write!(f, "off"),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct JobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub running: bool,
+    pub run_count: u64,
+    pub fail_count: u64,
+    pub last_start: Option<i64>,
+    pub last_duration_ms: Option<u64>,
+    pub last_ok: Option<bool>,
+    pub last_error: Option<String>,
+}
+
+struct JobSpec {
+    name: &'static str,
+    schedule: Schedule,
+    run_at_start: bool,
+    f: JobFn,
+}
+
+#[derive(Clone)]
+struct JobHandle {
+    trigger: Arc<Notify>,
+    status: Arc<Mutex<JobStatus>>,
+}
+
+pub struct Jobs {
+    handles: BTreeMap<&'static str, JobHandle>,
+}
+
+impl Jobs {
+    // 调度方式已在加载配置时检查过
+    pub fn start(rconn: &RdsConn, config: &AppConfig) -> Self {
+        let mut handles = BTreeMap::new();
+        for (name, default_schedule, run_at_start, f) in JOBS {
+            let s = config
+                .jobs
+                .get(name)
+                .map_or(default_schedule, String::as_str);
+            let schedule =
+                Schedule::parse(s).unwrap_or_else(|| panic!("invalid schedule {}={}", name, s));
+
+            let handle = JobHandle {
+                trigger: Arc::new(Notify::new()),
+                status: Arc::new(Mutex::new(JobStatus {
+                    name: name.to_string(),
+                    schedule: schedule.to_string(),
+                    ..JobStatus::default()
+                })),
+            };
+            let spec = JobSpec {
+                name,
+                schedule,
+                run_at_start: run_at_start && !matches!(schedule, Schedule::Off),
+                f,
+            };
+            tokio::spawn(Self::run_loop(
+                spec,
+                handle.clone(),
+                rconn.clone(),
+                config.database_url.clone(),
+            ));
+            handles.insert(name, handle);
+        }
+
+        Self { handles }
+    }
+
+    async fn run_loop(spec: JobSpec, handle: JobHandle, rconn: RdsConn, database_url: String) {
+        let JobSpec {
+            name,
+            schedule,
+            run_at_start,
+            f,
+        } = spec;
+        let JobHandle { trigger, status } = handle;
+        let mut conn: Option<JobConn> = None;
+        let mut first = true;
+        loop {
+            if !(first && run_at_start) {
+                match schedule.next_delay() {
+                    Some(d) => tokio::select! {
+                        _ = sleep(d) => {},
+                        _ = trigger.notified() => {},
+                    },
+                    None => trigger.notified().await,
+                }
+            }
+            first = false;
+
+            {
+                let mut st = status.lock().unwrap();
+                st.running = true;
+                st.last_start = Some(Local::now().timestamp());
+            }
+            debug!("job {} started", name);
+            let start = Instant::now();
+
+            // 在单独的 task 中运行，panic 不会影响之后的调度
+            // panic 时连接随 task 一起丢弃，下次运行时重新建立
+            let mut c = conn.take().unwrap_or_else(|| JobConn::new(&database_url));
+            let mut r = rconn.clone();
+            let result = tokio::spawn(async move {
+                let result = f(&mut c, &mut r).await;
+                (c, result)
+            })
+            .await;
+
+            let error = match result {
+                Ok((c, result)) => {
+                    conn = Some(c);
+                    result.err().map(|e| e.to_string())
+                }
+                Err(e) => Some(e.to_string()),
+            };
+            if let Some(e) = &error {
+                error!("job {} failed: {}", name, e);
+            }
+            let ok = error.is_none();
+            let elapsed = start.elapsed();
+            debug!("job {} finished in {:?}", name, elapsed);
+            JOB_RUNS.inc(format!(
+                "job=\"{}\",result=\"{}\"",
+                name,
+                if ok { "ok" } else { "failed" }
+            ));
+
+            let mut st = status.lock().unwrap();
+            st.running = false;
+            st.run_count += 1;
+            st.fail_count += u64::from(!ok);
+            st.last_duration_ms = Some(elapsed.as_millis().try_into().unwrap_or(u64::MAX));
+            st.last_ok = Some(ok);
+            st.last_error = error;
+        }
+    }
+
+    pub fn status(&self) -> Vec<JobStatus> {
+        self.handles
+            .values()
+            .map(|h| h.status.lock().unwrap().clone())
+            .collect()
+    }
+
+    // return false if job not found
+    pub fn trigger(&self, name: &str) -> bool {
+        self.handles
+            .get(name)
+            .map(|h| h.trigger.notify_one())
+            .is_some()
+    }
+}
@@ -3,7 +3,15 @@
  * change Connection to &mut Connection
  */
 
+use crate::metrics::DB_QUERY_LATENCY;
+use chrono::{offset::Local, DateTime};
+use log::Level;
+use rocket::serde::json::json;
+use rocket::serde::Serialize;
+use std::cell::RefCell;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 use diesel::backend::{Backend, UsesAnsiSavepointSyntax};
@@ -17,10 +25,10 @@ use diesel::sql_types::HasSqlType;
 /// Wraps a diesel `Connection` to time and log each query using
 /// the configured logger for the `log` crate.
 ///
-/// Currently, this produces a `debug` log on every query,
-/// an `info` on queries that take longer than 1 second,
-/// and a `warn`ing on queries that take longer than 5 seconds.
-/// These thresholds will be configurable in a future version.
+/// This produces a `debug` log on every query,
+/// an `info` on queries that take longer than `slow_query_info_ms`
+/// (default 1 second), and a `warn`ing on queries that take longer than
+/// `slow_query_warn_ms` (default 5 seconds). See [`init`].
 pub struct LoggingConnection<'r, C: Connection>(&'r mut C);
 
 impl<'r, C: Connection> LoggingConnection<'r, C> {
@@ -117,21 +125,116 @@ where
     }
 }
 
+static INFO_THRESHOLD_MS: AtomicU64 = AtomicU64::new(1000);
+static WARN_THRESHOLD_MS: AtomicU64 = AtomicU64::new(5000);
+static JSON_LOG: AtomicBool = AtomicBool::new(false);
+static TOP_N: AtomicUsize = AtomicUsize::new(20);
+static SLOW_QUERIES: Mutex<Vec<SlowQuery>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT_ROUTE: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Sets the slow query thresholds, how many slow queries to keep
+/// and whether to log queries as JSON.
+pub fn init(info_ms: u64, warn_ms: u64, top_n: usize, json: bool) {
+    INFO_THRESHOLD_MS.store(info_ms, Ordering::Relaxed);
+    WARN_THRESHOLD_MS.store(warn_ms, Ordering::Relaxed);
+    TOP_N.store(top_n, Ordering::Relaxed);
+    JSON_LOG.store(json, Ordering::Relaxed);
+}
+
+/// Runs `f` with the given route attached to every query logged on this thread.
+pub fn with_route<R>(route: Option<String>, f: impl FnOnce() -> R) -> R {
+    let old = CURRENT_ROUTE.with(|r| r.replace(route));
+    let result = f();
+    CURRENT_ROUTE.with(|r| r.replace(old));
+    result
+}
+
+/// A query slower than the `info` threshold.
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct SlowQuery {
+    pub query: String,
+    pub duration_ms: f32,
+    pub route: Option<String>,
+    pub time: DateTime<Local>,
+}
+
+/// The slowest queries since startup (or the last reset), slowest first.
+pub fn slow_queries() -> Vec<SlowQuery> {
+    SLOW_QUERIES.lock().unwrap().clone()
+}
+
+pub fn reset_slow_queries() {
+    SLOW_QUERIES.lock().unwrap().clear();
+}
+
+fn record_slow_query(sq: SlowQuery) {
+    let top_n = TOP_N.load(Ordering::Relaxed);
+    let mut list = SLOW_QUERIES.lock().unwrap();
+    if list.len() >= top_n
+        && list
+            .last()
+            .map_or(true, |last| last.duration_ms >= sq.duration_ms)
+    {
+        return;
+    }
+    let pos = list
+        .iter()
+        .position(|q| q.duration_ms < sq.duration_ms)
+        .unwrap_or(list.len());
+    list.insert(pos, sq);
+    list.truncate(top_n);
+}
+
 fn log_query(query: &str, duration: Duration) {
-    if duration.as_secs() >= 5 {
-        warn!(
-            "Slow query ran in {:.2} seconds: {}",
-            duration_to_secs(duration),
-            query
+    DB_QUERY_LATENCY.observe(duration);
+    let ms = duration.as_millis() as u64;
+    let level = if ms >= WARN_THRESHOLD_MS.load(Ordering::Relaxed) {
+        Level::Warn
+    } else if ms >= INFO_THRESHOLD_MS.load(Ordering::Relaxed) {
+        Level::Info
+    } else {
+        Level::Debug
+    };
+    if level == Level::Debug && !log_enabled!(Level::Debug) {
+        return;
+    }
+
+    let route = CURRENT_ROUTE.with(|r| r.borrow().clone());
+    if JSON_LOG.load(Ordering::Relaxed) {
+        log!(
+            level,
+            "{}",
+            json!({
+                "type": "query",
+                "slow": level != Level::Debug,
+                "duration_ms": duration_to_ms(duration),
+                "route": route,
+                "query": query,
+            })
         );
-    } else if duration.as_secs() >= 1 {
-        info!(
-            "Slow query ran in {:.2} seconds: {}",
+    } else if level == Level::Debug {
+        debug!("Query ran in {:.1} ms: {}", duration_to_ms(duration), query);
+    } else {
+        log!(
+            level,
+            "Slow query ran in {:.2} seconds ({}): {}",
             duration_to_secs(duration),
+            route.as_deref().unwrap_or("-"),
             query
         );
-    } else {
-        debug!("Query ran in {:.1} ms: {}", duration_to_ms(duration), query);
+    }
+
+    if level != Level::Debug {
+        record_slow_query(SlowQuery {
+            query: query.to_string(),
+            duration_ms: duration_to_ms(duration),
+            route,
+            time: Local::now(),
+        });
     }
 }
 
@@ -1,15 +1,48 @@
 #![allow(clippy::unused_unit)]
 
+use crate::config::AppConfig;
 use crate::db_conn::Db;
+use crate::i18n::{Lang, Msg};
 use crate::models::User;
 use crate::random_hasher::RandomHasher;
+use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::response::Redirect;
 use rocket::serde::Deserialize;
 use rocket::State;
-use std::env;
 use url::Url;
 
+fn mast_config(config: &AppConfig, lang: Lang) -> Result<(&str, &str, &str), &'static str> {
+    match (
+        &config.mast_base_url,
+        &config.mast_client,
+        &config.mast_secret,
+    ) {
+        (Some(url), Some(cli), Some(sec)) => Ok((url.as_str(), cli.as_str(), sec.as_str())),
+        _ => Err(Msg::MastNotConfigured.text(lang)),
+    }
+}
+
+fn gh_config(config: &AppConfig, lang: Lang) -> Result<(&str, &str), &'static str> {
+    match (&config.gh_client, &config.gh_secret) {
+        (Some(cli), Some(sec)) => Ok((cli.as_str(), sec.as_str())),
+        _ => Err(Msg::GhNotConfigured.text(lang)),
+    }
+}
+
+fn check_jump_to_url(
+    config: &AppConfig,
+    jump_to_url: &str,
+    lang: Lang,
+) -> Result<(), &'static str> {
+    config
+        .frontend_whitelist
+        .iter()
+        .any(|url| jump_to_url.starts_with(url))
+        .then_some(())
+        .ok_or(Msg::FrontendNotWhitelisted.text(lang))
+}
+
 #[derive(Debug)]
 pub struct FrontendAddr(pub String);
 
@@ -17,13 +50,16 @@ pub struct FrontendAddr(pub String);
 impl<'r> FromRequest<'r> for FrontendAddr {
     type Error = ();
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        Outcome::Success(Self(
-            request
-                .headers()
-                .get_one("Referer")
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| env::var("DEFAULT_FRONTEND").unwrap()),
-        ))
+        let config = request.rocket().state::<AppConfig>().unwrap();
+        match request
+            .headers()
+            .get_one("Referer")
+            .map(|s| s.to_string())
+            .or_else(|| config.default_frontend.clone())
+        {
+            Some(addr) => Outcome::Success(Self(addr)),
+            None => Outcome::Failure((Status::BadRequest, ())),
+        }
     }
 }
 
@@ -34,21 +70,27 @@ pub struct BackendAddr(pub String);
 impl<'r> FromRequest<'r> for BackendAddr {
     type Error = ();
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        Outcome::Success(Self(
-            request
-                .headers()
-                .get_one("Host")
-                .map(|s| format!("https://{}", s))
-                .unwrap_or_else(|| env::var("DEFAULT_BACKEND").unwrap()),
-        ))
+        let config = request.rocket().state::<AppConfig>().unwrap();
+        match request
+            .headers()
+            .get_one("Host")
+            .map(|s| format!("https://{}", s))
+            .or_else(|| config.default_backend.clone())
+        {
+            Some(addr) => Outcome::Success(Self(addr)),
+            None => Outcome::Failure((Status::BadRequest, ())),
+        }
     }
 }
 
 #[get("/?p=cs")]
-pub fn cs_login(r: FrontendAddr, h: BackendAddr) -> Redirect {
-    let mast_url = env::var("MAST_BASE_URL").unwrap();
-    let mast_cli = env::var("MAST_CLIENT").unwrap();
-    let mast_scope = env::var("MAST_SCOPE").unwrap();
+pub fn cs_login(
+    r: FrontendAddr,
+    h: BackendAddr,
+    config: &State<AppConfig>,
+    lang: Lang,
+) -> Result<Redirect, &'static str> {
+    let (mast_url, mast_cli, _) = mast_config(config, lang)?;
 
     let jump_to_url = Url::parse(&r.0).unwrap();
     let mut redirect_url = Url::parse(&h.0).unwrap();
@@ -67,14 +109,14 @@ pub fn cs_login(r: FrontendAddr, h: BackendAddr) -> Redirect {
         &format!("{}oauth/authorize", mast_url),
         &[
             ("redirect_uri", redirect_url.as_str()),
-            ("client_id", &mast_cli),
-            ("scope", &mast_scope),
+            ("client_id", mast_cli),
+            ("scope", config.mast_scope.as_str()),
             ("response_type", "code"),
         ],
     )
     .unwrap();
 
-    Redirect::to(url.to_string())
+    Ok(Redirect::to(url.to_string()))
 }
 
 #[derive(Deserialize, Debug)]
@@ -95,19 +137,11 @@ pub async fn cs_auth(
     jump_to_url: String,
     db: Db,
     rh: &State<RandomHasher>,
+    config: &State<AppConfig>,
+    lang: Lang,
 ) -> Result<Redirect, &'static str> {
-    if !env::var("FRONTEND_WHITELIST")
-        .unwrap_or_default()
-        .split(',')
-        .any(|url| jump_to_url.starts_with(url))
-    {
-        return Err("前端地址不在白名单内");
-    }
-
-    let mast_url = env::var("MAST_BASE_URL").unwrap();
-    let mast_cli = env::var("MAST_CLIENT").unwrap();
-    let mast_sec = env::var("MAST_SECRET").unwrap();
-    let mast_scope = env::var("MAST_SCOPE").unwrap();
+    check_jump_to_url(config, &jump_to_url, lang)?;
+    let (mast_url, mast_cli, mast_sec) = mast_config(config, lang)?;
 
     // to keep same
     let redirect_url = Url::parse_with_params(
@@ -121,11 +155,11 @@ pub async fn cs_auth(
 
     let client = reqwest::Client::new();
     let r = client
-        .post(format!("{}oauth/token", &mast_url))
+        .post(format!("{}oauth/token", mast_url))
         .form(&[
-            ("client_id", mast_cli.as_str()),
-            ("client_secret", mast_sec.as_str()),
-            ("scope", mast_scope.as_str()),
+            ("client_id", mast_cli),
+            ("client_secret", mast_sec),
+            ("scope", config.mast_scope.as_str()),
             ("redirect_uri", redirect_url.as_str()),
             ("grant_type", "authorization_code"),
             ("code", code.as_str()),
@@ -140,7 +174,7 @@ pub async fn cs_auth(
 
     let client = reqwest::Client::new();
     let account = client
-        .get(format!("{}api/v1/accounts/verify_credentials", &mast_url))
+        .get(format!("{}api/v1/accounts/verify_credentials", mast_url))
         .bearer_auth(token.access_token)
         .send()
         .await
@@ -163,9 +197,14 @@ pub async fn cs_auth(
 }
 
 #[get("/gh")]
-pub fn gh_login(r: FrontendAddr, h: BackendAddr) -> Redirect {
+pub fn gh_login(
+    r: FrontendAddr,
+    h: BackendAddr,
+    config: &State<AppConfig>,
+    lang: Lang,
+) -> Result<Redirect, &'static str> {
     let gh_url = "https://github.com/login/oauth/authorize";
-    let gh_cli = env::var("GH_CLIENT").unwrap();
+    let (gh_cli, _) = gh_config(config, lang)?;
     let gh_scope = "user:email";
 
     let jump_to_url = Url::parse(&r.0).unwrap();
@@ -182,13 +221,13 @@ pub fn gh_login(r: FrontendAddr, h: BackendAddr) -> Redirect {
         gh_url,
         &[
             ("redirect_uri", redirect_url.as_str()),
-            ("client_id", &gh_cli),
+            ("client_id", gh_cli),
             ("scope", gh_scope),
         ],
     )
     .unwrap();
 
-    Redirect::to(url.to_string())
+    Ok(Redirect::to(url.to_string()))
 }
 
 #[derive(Deserialize, Debug)]
@@ -204,25 +243,19 @@ pub async fn gh_auth(
     jump_to_url: String,
     db: Db,
     rh: &State<RandomHasher>,
+    config: &State<AppConfig>,
+    lang: Lang,
 ) -> Result<Redirect, &'static str> {
-    if !env::var("FRONTEND_WHITELIST")
-        .unwrap_or_default()
-        .split(',')
-        .any(|url| jump_to_url.starts_with(url))
-    {
-        return Err("前端地址不在白名单内");
-    }
-
-    let gh_cli = env::var("GH_CLIENT").unwrap();
-    let gh_sec = env::var("GH_SECRET").unwrap();
+    check_jump_to_url(config, &jump_to_url, lang)?;
+    let (gh_cli, gh_sec) = gh_config(config, lang)?;
 
     let client = reqwest::Client::new();
     let r = client
         .post("https://github.com/login/oauth/access_token")
         .header(reqwest::header::ACCEPT, "application/json")
         .form(&[
-            ("client_id", gh_cli.as_str()),
-            ("client_secret", gh_sec.as_str()),
+            ("client_id", gh_cli),
+            ("client_secret", gh_sec),
             ("code", code.as_str()),
         ])
         .send()
@@ -269,5 +302,5 @@ pub async fn gh_auth(
         }
     }
 
-    Err("没有找到已验证的清华邮箱")
+    Err(Msg::NoThuEmail.text(lang))
 }
@@ -12,59 +12,93 @@ extern crate log;
 
 mod api;
 mod cache;
+mod cli;
+mod config;
 mod cors;
 mod db_conn;
+mod health;
+mod i18n;
+mod jobs;
 mod libs;
 #[cfg(feature = "mastlogin")]
 mod login;
+mod mem_store;
+mod metrics;
+#[cfg(feature = "migdb")]
+mod migdb;
 mod models;
 mod random_hasher;
 mod rds_conn;
 mod rds_models;
 mod schema;
+#[cfg(test)]
+mod tests;
 
-use db_conn::{establish_connection, Conn, Db};
+use api::attention::PushKey;
+use config::AppConfig;
+use db_conn::{establish_connection, Conn, DbPool};
 use diesel::Connection;
 use random_hasher::RandomHasher;
-use rds_conn::{init_rds_client, RdsConn};
+use rds_conn::RdsConn;
 use rds_models::clear_outdate_redis_data;
-use rocket::tokio;
-use rocket::tokio::time::{sleep, Duration};
+use rocket::figment::Figment;
+use rocket::{Build, Rocket};
 use std::env;
+use std::fs;
 
 embed_migrations!("migrations/postgres");
 
 #[rocket::main]
 async fn main() {
     load_env();
-    if env::args().any(|arg| arg.eq("--init-database")) {
-        init_database();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg.eq("--init-database")) {
+        init_database(&AppConfig::load_database_url());
         return;
     }
+    let (config, figment) = AppConfig::load();
     env_logger::init();
-    let rmc = init_rds_client().await;
-    let mut rconn = RdsConn(rmc.clone());
-    let mut c_start = establish_connection();
+    libs::diesel_logger::init(
+        config.slow_query_info_ms,
+        config.slow_query_warn_ms,
+        config.slow_query_top_n,
+        config.query_log_json,
+    );
+    models::HotDecay::set(models::HotDecay::from_config(&config));
+    if cli::run(&args, &config).await {
+        return;
+    }
+    let (rconn, rh) = prepare(&config).await;
+    let _ = build_rocket(figment, config, rconn, rh).launch().await;
+}
+
+// 启动前创建上传目录，初始化 salt，清理过期数据，导入旧数据并创建房间
+async fn prepare(config: &AppConfig) -> (RdsConn, RandomHasher) {
+    fs::create_dir_all(&config.upload_dir)
+        .unwrap_or_else(|e| panic!("create upload_dir {} failed: {}", config.upload_dir, e));
+    let mut rconn = RdsConn::connect(config).await;
+    let rh = RandomHasher::init(&rconn).await;
+    let mut c_start = establish_connection(&config.database_url);
     models::User::clear_non_admin_users(&mut c_start, &mut rconn).await;
     clear_outdate_redis_data(&mut rconn).await;
-    tokio::spawn(async move {
-        loop {
-            sleep(Duration::from_secs(3 * 60 * 60)).await;
-            models::Post::annealing(&mut c_start, &mut rconn).await;
-        }
-    });
+    models::Poll::import_legacy(&mut c_start, &rconn).await;
+    models::Room::seed(&mut c_start, &rconn, &config.rooms).await;
+    (rconn, rh)
+}
 
-    let rconn = RdsConn(rmc.clone());
-    tokio::spawn(async move {
-        loop {
-            for room_id in (0..5).map(Some).chain([None, Some(42)]) {
-                cache::PostListCache::init(room_id, 3, &rconn).clear().await;
-            }
-            sleep(Duration::from_secs(5 * 60)).await;
-        }
-    });
+fn build_rocket(
+    figment: Figment,
+    config: AppConfig,
+    rconn: RdsConn,
+    rh: RandomHasher,
+) -> Rocket<Build> {
+    let jobs = jobs::Jobs::start(&rconn, &config);
+    let push_key = PushKey::load(&config.push_private_key);
+    let cors = cors::Cors {
+        whitelist: config.frontend_whitelist.clone(),
+    };
 
-    let _ = rocket::build()
+    let rocket = rocket::custom(figment)
         .mount(
             "/_api/v1",
             routes![
@@ -82,6 +116,7 @@ async fn main() {
                 api::operation::report,
                 api::operation::block,
                 api::operation::set_auto_block,
+                api::operation::set_lang,
                 api::vote::vote,
                 cors::options_handler,
             ],
@@ -90,7 +125,28 @@ async fn main() {
             "/_api/v2",
             routes![
                 api::attention::set_notification,
+                api::vote::export_poll,
+                api::room::get_rooms,
+                api::room::set_room,
+                api::room::delete_room,
+                api::post::pin_post,
+                api::post::lock_post,
+                api::post::get_scheduled,
+                api::post::cancel_scheduled,
+                api::draft::save_draft,
+                api::draft::list_drafts,
+                api::draft::load_draft,
+                api::draft::delete_draft,
+                api::job::get_jobs,
+                api::job::run_job,
+                api::cache::get_cache_metrics,
+                api::cache::inspect_post,
+                api::cache::flush_cache,
+                api::slow_query::get_slow_queries,
+                api::slow_query::reset_slow_queries,
                 api::reaction::reaction,
+                api::reaction::emoji_reaction,
+                api::reaction::get_emoji_set,
                 api::comment::add_comment,
                 api::operation::set_title,
                 api::upload::local_upload,
@@ -111,26 +167,33 @@ async fn main() {
             ]
             .concat(),
         )
+        .mount(
+            "/",
+            routes![metrics::metrics, health::health, health::ready],
+        )
+        .mount("/_api", routes![api::openapi::openapi])
         .register(
             "/_api",
             catchers![
                 api::catch_401_error,
                 api::catch_403_error,
-                api::catch_404_error
+                api::catch_404_error,
+                api::catch_422_error,
+                api::catch_503_error
             ],
-        )
-        .manage(RandomHasher::get_random_one())
-        .manage(rmc)
-        .attach(Db::fairing())
-        .attach(cors::Cors {
-            whitelist: env::var("FRONTEND_WHITELIST")
-                .unwrap_or_default()
-                .split(',')
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>(),
-        })
-        .launch()
-        .await;
+        );
+    let doc = api::openapi::generate(&rocket);
+
+    rocket
+        .manage(doc)
+        .manage(rh)
+        .manage(rconn)
+        .manage(jobs)
+        .manage(push_key)
+        .manage(config)
+        .attach(DbPool::fairing())
+        .attach(metrics::Metrics)
+        .attach(cors)
 }
 
 fn load_env() {
@@ -141,8 +204,7 @@ fn load_env() {
     }
 }
 
-fn init_database() {
-    let database_url = env::var("DATABASE_URL").unwrap();
-    let conn = Conn::establish(&database_url).unwrap();
+fn init_database(database_url: &str) {
+    let conn = Conn::establish(database_url).unwrap();
     embedded_migrations::run(&conn).unwrap();
 }
@@ -0,0 +1,553 @@
+// 不使用 redis 时的进程内存储，适合单实例部署和开发
+// 直接解释 redis 命令，只实现了项目中用到的命令，调用方不需要区分后端
+// 数据不持久化，重启后丢失，多个实例之间也不共享
+use redis::{Arg, Cmd, ErrorKind, RedisError, RedisResult, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+type Bytes = Vec<u8>;
+
+// 每执行这么多条命令清理一次过期的 key，其余时候只在访问时检查
+const SWEEP_INTERVAL: usize = 1000;
+
+enum Data {
+    Str(Bytes),
+    Set(BTreeSet<Bytes>),
+    Hash(BTreeMap<Bytes, Bytes>),
+    // 按 (score, member) 有序
+    ZSet(Vec<(f64, Bytes)>),
+    List(VecDeque<Bytes>),
+}
+
+struct Entry {
+    data: Data,
+    expire_at: Option<Instant>,
+}
+
+impl Entry {
+    fn expired(&self, now: Instant) -> bool {
+        self.expire_at.map_or(false, |t| t <= now)
+    }
+}
+
+#[derive(Default)]
+pub struct MemStore {
+    entries: Mutex<HashMap<Bytes, Entry>>,
+    ops: AtomicUsize,
+}
+
+fn err(msg: &'static str) -> RedisError {
+    RedisError::from((ErrorKind::ResponseError, msg))
+}
+
+fn wrong_type() -> RedisError {
+    RedisError::from((
+        ErrorKind::TypeError,
+        "WRONGTYPE Operation against a key holding the wrong kind of value",
+    ))
+}
+
+fn wrong_args() -> RedisError {
+    err("wrong number of arguments")
+}
+
+fn parse<T: std::str::FromStr>(arg: &[u8]) -> RedisResult<T> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| err("value is not a number or out of range"))
+}
+
+fn data(b: &[u8]) -> Value {
+    Value::Data(b.to_vec())
+}
+
+fn bulk<'a>(items: impl Iterator<Item = &'a Bytes>) -> Value {
+    Value::Bulk(items.map(|b| data(b)).collect())
+}
+
+fn fmt_score(score: f64) -> Value {
+    Value::Data(score.to_string().into_bytes())
+}
+
+// ZRANGEBYSCORE 的边界，"(" 开头表示不包含，支持 -inf 和 +inf
+struct ScoreBound {
+    value: f64,
+    exclusive: bool,
+}
+
+impl ScoreBound {
+    fn below(&self, score: f64) -> bool {
+        if self.exclusive {
+            self.value < score
+        } else {
+            self.value <= score
+        }
+    }
+
+    fn above(&self, score: f64) -> bool {
+        if self.exclusive {
+            score < self.value
+        } else {
+            score <= self.value
+        }
+    }
+}
+
+fn score_bound(arg: &[u8]) -> RedisResult<ScoreBound> {
+    match arg.strip_prefix(b"(") {
+        Some(rest) => Ok(ScoreBound {
+            value: parse(rest)?,
+            exclusive: true,
+        }),
+        None => Ok(ScoreBound {
+            value: parse(arg)?,
+            exclusive: false,
+        }),
+    }
+}
+
+// 把 redis 风格的下标(可以为负数)转换成 [start, end) 区间
+fn index_range(start: i64, stop: i64, len: usize) -> (usize, usize) {
+    let len = len as i64;
+    let norm = |i: i64| if i < 0 { len + i } else { i };
+    let start = norm(start).max(0);
+    let stop = norm(stop).min(len - 1);
+    if start > stop {
+        (0, 0)
+    } else {
+        (start as usize, stop as usize + 1)
+    }
+}
+
+// 支持 * 和 ?，够 SCAN MATCH 使用
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| glob_match(rest, &s[i..])),
+        Some((b'?', rest)) => !s.is_empty() && glob_match(rest, &s[1..]),
+        Some((c, rest)) => s.first() == Some(c) && glob_match(rest, &s[1..]),
+    }
+}
+
+macro_rules! get_as {
+    ($entries:expr, $key:expr, $variant:ident) => {
+        match $entries.get($key) {
+            Some(Entry {
+                data: Data::$variant(v),
+                ..
+            }) => Some(v),
+            Some(_) => return Err(wrong_type()),
+            None => None,
+        }
+    };
+}
+
+macro_rules! get_or_insert_as {
+    ($entries:expr, $key:expr, $variant:ident) => {
+        match &mut $entries
+            .entry($key.to_vec())
+            .or_insert_with(|| Entry {
+                data: Data::$variant(Default::default()),
+                expire_at: None,
+            })
+            .data
+        {
+            Data::$variant(v) => v,
+            _ => return Err(wrong_type()),
+        }
+    };
+}
+
+impl MemStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn query(&self, cmd: &Cmd) -> RedisResult<Value> {
+        let args: Vec<&[u8]> = cmd
+            .args_iter()
+            .map(|arg| match arg {
+                Arg::Simple(a) => a,
+                // 一次返回所有结果，游标总是 0
+                Arg::Cursor => &b"0"[..],
+            })
+            .collect();
+        let (name, args) = args.split_first().ok_or_else(wrong_args)?;
+        let name = String::from_utf8_lossy(name).to_ascii_uppercase();
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if self.ops.fetch_add(1, Ordering::Relaxed) % SWEEP_INTERVAL == 0 {
+            entries.retain(|_, e| !e.expired(now));
+        }
+        // 先移除本条命令涉及的已过期 key
+        let keys = match name.as_str() {
+            "DEL" | "EXISTS" | "MGET" => args,
+            "PING" | "SCAN" => &[][..],
+            _ => args.get(..1).unwrap_or_default(),
+        };
+        for key in keys {
+            if entries.get(*key).map_or(false, |e| e.expired(now)) {
+                entries.remove(*key);
+            }
+        }
+
+        let r = exec(&mut entries, &name, args, now);
+
+        // 集合类型的值为空时 redis 会删除这个 key
+        if let Some(key) = args.first() {
+            let empty = match entries.get(*key).map(|e| &e.data) {
+                Some(Data::Set(v)) => v.is_empty(),
+                Some(Data::Hash(v)) => v.is_empty(),
+                Some(Data::ZSet(v)) => v.is_empty(),
+                Some(Data::List(v)) => v.is_empty(),
+                _ => false,
+            };
+            if empty {
+                entries.remove(*key);
+            }
+        }
+        r
+    }
+}
+
+fn exec(
+    entries: &mut HashMap<Bytes, Entry>,
+    name: &str,
+    args: &[&[u8]],
+    now: Instant,
+) -> RedisResult<Value> {
+    let expire_in = |secs: &[u8]| -> RedisResult<Instant> {
+        Ok(now + Duration::from_secs(parse::<u64>(secs)?))
+    };
+
+    Ok(match (name, args) {
+        ("PING", []) => Value::Status("PONG".to_string()),
+
+        // string
+        ("GET", [key]) => get_as!(entries, *key, Str).map_or(Value::Nil, |v| data(v)),
+        ("SET", [key, value, opts @ ..]) => {
+            let expire_at = match opts {
+                [] => None,
+                [ex, secs] if ex.eq_ignore_ascii_case(b"EX") => Some(expire_in(secs)?),
+                _ => return Err(err("unsupported SET option")),
+            };
+            entries.insert(
+                key.to_vec(),
+                Entry {
+                    data: Data::Str(value.to_vec()),
+                    expire_at,
+                },
+            );
+            Value::Okay
+        }
+        ("MGET", keys) if !keys.is_empty() => Value::Bulk(
+            keys.iter()
+                .map(|k| match entries.get(*k) {
+                    Some(Entry {
+                        data: Data::Str(v), ..
+                    }) => data(v),
+                    _ => Value::Nil,
+                })
+                .collect(),
+        ),
+        ("MSET", kvs) if !kvs.is_empty() && kvs.len() % 2 == 0 => {
+            for kv in kvs.chunks(2) {
+                entries.insert(
+                    kv[0].to_vec(),
+                    Entry {
+                        data: Data::Str(kv[1].to_vec()),
+                        expire_at: None,
+                    },
+                );
+            }
+            Value::Okay
+        }
+        ("SETEX", [key, secs, value]) => {
+            let expire_at = Some(expire_in(secs)?);
+            entries.insert(
+                key.to_vec(),
+                Entry {
+                    data: Data::Str(value.to_vec()),
+                    expire_at,
+                },
+            );
+            Value::Okay
+        }
+
+        // key
+        ("DEL", keys) if !keys.is_empty() => Value::Int(
+            keys.iter()
+                .filter(|k| entries.remove(**k).is_some())
+                .count() as i64,
+        ),
+        ("EXISTS", keys) if !keys.is_empty() => {
+            Value::Int(keys.iter().filter(|k| entries.contains_key(**k)).count() as i64)
+        }
+        ("EXPIRE", [key, secs]) => {
+            let expire_at = expire_in(secs)?;
+            match entries.get_mut(*key) {
+                Some(e) => {
+                    e.expire_at = Some(expire_at);
+                    Value::Int(1)
+                }
+                None => Value::Int(0),
+            }
+        }
+        ("TTL", [key]) => Value::Int(match entries.get(*key) {
+            None => -2,
+            Some(Entry {
+                expire_at: None, ..
+            }) => -1,
+            Some(Entry {
+                expire_at: Some(t), ..
+            }) => t.saturating_duration_since(now).as_secs() as i64,
+        }),
+        ("SCAN", [_cursor, opts @ ..]) => {
+            let pattern = match opts {
+                [] => &b"*"[..],
+                [m, p, ..] if m.eq_ignore_ascii_case(b"MATCH") => p,
+                _ => return Err(err("unsupported SCAN option")),
+            };
+            let keys = entries
+                .iter()
+                .filter(|(k, e)| !e.expired(now) && glob_match(pattern, k))
+                .map(|(k, _)| k);
+            Value::Bulk(vec![data(b"0"), bulk(keys)])
+        }
+
+        // set
+        ("SADD", [key, members @ ..]) if !members.is_empty() => {
+            let set = get_or_insert_as!(entries, key, Set);
+            Value::Int(members.iter().filter(|m| set.insert(m.to_vec())).count() as i64)
+        }
+        ("SREM", [key, members @ ..]) if !members.is_empty() => match entries.get_mut(*key) {
+            Some(Entry {
+                data: Data::Set(s), ..
+            }) => Value::Int(members.iter().filter(|m| s.remove(**m)).count() as i64),
+            Some(_) => return Err(wrong_type()),
+            None => Value::Int(0),
+        },
+        ("SISMEMBER", [key, member]) => {
+            Value::Int(get_as!(entries, *key, Set).map_or(false, |s| s.contains(*member)) as i64)
+        }
+        ("SMEMBERS", [key]) => match get_as!(entries, *key, Set) {
+            Some(s) => bulk(s.iter()),
+            None => Value::Bulk(vec![]),
+        },
+        ("SCARD", [key]) => Value::Int(get_as!(entries, *key, Set).map_or(0, |s| s.len()) as i64),
+
+        // hash
+        ("HSET" | "HMSET", [key, fvs @ ..]) if !fvs.is_empty() && fvs.len() % 2 == 0 => {
+            let hash = get_or_insert_as!(entries, key, Hash);
+            let added = fvs
+                .chunks(2)
+                .filter(|fv| hash.insert(fv[0].to_vec(), fv[1].to_vec()).is_none())
+                .count();
+            if name == "HMSET" {
+                Value::Okay
+            } else {
+                Value::Int(added as i64)
+            }
+        }
+        ("HGET", [key, field]) => get_as!(entries, *key, Hash)
+            .and_then(|h| h.get(*field))
+            .map_or(Value::Nil, |v| data(v)),
+        ("HGETALL", [key]) => match get_as!(entries, *key, Hash) {
+            Some(h) => bulk(h.iter().flat_map(|(f, v)| [f, v])),
+            None => Value::Bulk(vec![]),
+        },
+        ("HDEL", [key, fields @ ..]) if !fields.is_empty() => match entries.get_mut(*key) {
+            Some(Entry {
+                data: Data::Hash(h),
+                ..
+            }) => Value::Int(fields.iter().filter(|f| h.remove(**f).is_some()).count() as i64),
+            Some(_) => return Err(wrong_type()),
+            None => Value::Int(0),
+        },
+        ("HLEN", [key]) => Value::Int(get_as!(entries, *key, Hash).map_or(0, |h| h.len()) as i64),
+        ("HEXISTS", [key, field]) => Value::Int(
+            get_as!(entries, *key, Hash).map_or(false, |h| h.contains_key(*field)) as i64,
+        ),
+        ("HINCRBY", [key, field, delta]) => {
+            let delta: i64 = parse(delta)?;
+            let hash = get_or_insert_as!(entries, key, Hash);
+            let n = match hash.get(*field) {
+                Some(v) => parse::<i64>(v)?,
+                None => 0,
+            } + delta;
+            hash.insert(field.to_vec(), n.to_string().into_bytes());
+            Value::Int(n)
+        }
+
+        // list
+        ("LPUSH", [key, values @ ..]) if !values.is_empty() => {
+            let list = get_or_insert_as!(entries, key, List);
+            for v in values.iter() {
+                list.push_front(v.to_vec());
+            }
+            Value::Int(list.len() as i64)
+        }
+        ("RPUSH", [key, values @ ..]) if !values.is_empty() => {
+            let list = get_or_insert_as!(entries, key, List);
+            list.extend(values.iter().map(|v| v.to_vec()));
+            Value::Int(list.len() as i64)
+        }
+        ("LRANGE", [key, start, stop]) => match get_as!(entries, *key, List) {
+            Some(l) => {
+                let (s, e) = index_range(parse(start)?, parse(stop)?, l.len());
+                bulk(l.range(s..e))
+            }
+            None => Value::Bulk(vec![]),
+        },
+        ("LTRIM", [key, start, stop]) => {
+            let (start, stop) = (parse(start)?, parse(stop)?);
+            if let Some(Entry {
+                data: Data::List(l),
+                ..
+            }) = entries.get_mut(*key)
+            {
+                let (s, e) = index_range(start, stop, l.len());
+                l.truncate(e);
+                l.drain(..s);
+            }
+            Value::Okay
+        }
+        ("LLEN", [key]) => Value::Int(get_as!(entries, *key, List).map_or(0, |l| l.len()) as i64),
+
+        // sorted set
+        ("ZADD", [key, sms @ ..]) if !sms.is_empty() && sms.len() % 2 == 0 => {
+            let items = sms
+                .chunks(2)
+                .map(|sm| Ok((parse::<f64>(sm[0])?, sm[1].to_vec())))
+                .collect::<RedisResult<Vec<_>>>()?;
+            let zset = get_or_insert_as!(entries, key, ZSet);
+            let mut added = 0;
+            for (score, member) in items {
+                match zset.iter().position(|(_, m)| *m == member) {
+                    Some(i) => {
+                        zset.remove(i);
+                    }
+                    None => added += 1,
+                }
+                let i = zset.partition_point(|(s, m)| (*s, m) < (score, &member));
+                zset.insert(i, (score, member));
+            }
+            Value::Int(added)
+        }
+        ("ZREM", [key, members @ ..]) if !members.is_empty() => match entries.get_mut(*key) {
+            Some(Entry {
+                data: Data::ZSet(z),
+                ..
+            }) => {
+                let len = z.len();
+                z.retain(|(_, m)| !members.contains(&m.as_slice()));
+                Value::Int((len - z.len()) as i64)
+            }
+            Some(_) => return Err(wrong_type()),
+            None => Value::Int(0),
+        },
+        ("ZCARD", [key]) => Value::Int(get_as!(entries, *key, ZSet).map_or(0, |z| z.len()) as i64),
+        ("ZSCORE", [key, member]) => get_as!(entries, *key, ZSet)
+            .and_then(|z| z.iter().find(|(_, m)| m == member))
+            .map_or(Value::Nil, |(s, _)| fmt_score(*s)),
+        ("ZRANGE", [key, start, stop, opts @ ..]) => {
+            let with_scores = match opts {
+                [] => false,
+                [w] if w.eq_ignore_ascii_case(b"WITHSCORES") => true,
+                _ => return Err(err("unsupported ZRANGE option")),
+            };
+            match get_as!(entries, *key, ZSet) {
+                Some(z) => {
+                    let (s, e) = index_range(parse(start)?, parse(stop)?, z.len());
+                    Value::Bulk(
+                        z[s..e]
+                            .iter()
+                            .flat_map(|(score, m)| {
+                                let mut v = vec![data(m)];
+                                if with_scores {
+                                    v.push(fmt_score(*score));
+                                }
+                                v
+                            })
+                            .collect(),
+                    )
+                }
+                None => Value::Bulk(vec![]),
+            }
+        }
+        ("ZCOUNT", [key, min, max]) => {
+            let (min, max) = (score_bound(min)?, score_bound(max)?);
+            Value::Int(get_as!(entries, *key, ZSet).map_or(0, |z| {
+                z.iter()
+                    .filter(|(score, _)| min.below(*score) && max.above(*score))
+                    .count()
+            }) as i64)
+        }
+        ("ZRANGEBYSCORE", [key, min, max, opts @ ..]) => {
+            let mut opts = opts;
+            let mut with_scores = false;
+            // count 为负数时不限数量
+            let mut limit = (0, usize::MAX);
+            loop {
+                match opts {
+                    [] => break,
+                    [w, rest @ ..] if w.eq_ignore_ascii_case(b"WITHSCORES") => {
+                        with_scores = true;
+                        opts = rest;
+                    }
+                    [l, offset, count, rest @ ..] if l.eq_ignore_ascii_case(b"LIMIT") => {
+                        let count: i64 = parse(count)?;
+                        limit = (parse(offset)?, usize::try_from(count).unwrap_or(usize::MAX));
+                        opts = rest;
+                    }
+                    _ => return Err(err("unsupported ZRANGEBYSCORE option")),
+                }
+            }
+            let (min, max) = (score_bound(min)?, score_bound(max)?);
+            match get_as!(entries, *key, ZSet) {
+                Some(z) => Value::Bulk(
+                    z.iter()
+                        .filter(|(score, _)| min.below(*score) && max.above(*score))
+                        .skip(limit.0)
+                        .take(limit.1)
+                        .flat_map(|(score, m)| {
+                            let mut v = vec![data(m)];
+                            if with_scores {
+                                v.push(fmt_score(*score));
+                            }
+                            v
+                        })
+                        .collect(),
+                ),
+                None => Value::Bulk(vec![]),
+            }
+        }
+        ("ZREMRANGEBYRANK", [key, start, stop]) => {
+            let (start, stop) = (parse(start)?, parse(stop)?);
+            match entries.get_mut(*key) {
+                Some(Entry {
+                    data: Data::ZSet(z),
+                    ..
+                }) => {
+                    let (s, e) = index_range(start, stop, z.len());
+                    z.drain(s..e);
+                    Value::Int((e - s) as i64)
+                }
+                Some(_) => return Err(wrong_type()),
+                None => Value::Int(0),
+            }
+        }
+
+        _ => {
+            return Err(RedisError::from((
+                ErrorKind::ResponseError,
+                "unsupported command for in-memory store",
+                name.to_string(),
+            )))
+        }
+    })
+}
@@ -0,0 +1,247 @@
+// Prometheus 格式的监控数据，不引入额外的依赖，手动拼接文本
+use crate::cache::{cache_metrics, CacheMetricsSnapshot};
+use crate::config::AppConfig;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Clone)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, secs: f64) {
+        for (i, le) in BUCKETS.iter().enumerate() {
+            if secs <= *le {
+                self.buckets[i] += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (le, n) in BUCKETS.iter().zip(self.buckets.iter()) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, sep, le, n
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, sep, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+// 标签组合不多，用 Vec 就够了
+pub struct LabeledCounter(Mutex<Vec<(String, u64)>>);
+
+impl LabeledCounter {
+    const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    pub fn inc(&self, labels: String) {
+        let mut v = self.0.lock().unwrap();
+        match v.iter_mut().find(|(l, _)| l == &labels) {
+            Some((_, n)) => *n += 1,
+            None => v.push((labels, 1)),
+        }
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        for (labels, n) in self.0.lock().unwrap().iter() {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, n);
+        }
+    }
+}
+
+pub struct LabeledHistogram(Mutex<Vec<(String, Histogram)>>);
+
+impl LabeledHistogram {
+    const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    pub fn observe(&self, labels: String, d: Duration) {
+        let mut v = self.0.lock().unwrap();
+        match v.iter_mut().find(|(l, _)| l == &labels) {
+            Some((_, h)) => h.observe(d.as_secs_f64()),
+            None => {
+                let mut h = Histogram::new();
+                h.observe(d.as_secs_f64());
+                v.push((labels, h));
+            }
+        }
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        for (labels, h) in self.0.lock().unwrap().iter() {
+            h.render(out, name, labels);
+        }
+    }
+}
+
+pub struct SimpleHistogram(Mutex<Histogram>);
+
+impl SimpleHistogram {
+    const fn new() -> Self {
+        Self(Mutex::new(Histogram::new()))
+    }
+
+    pub fn observe(&self, d: Duration) {
+        self.0.lock().unwrap().observe(d.as_secs_f64());
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        self.0.lock().unwrap().render(out, name, "");
+    }
+}
+
+pub static HTTP_REQUESTS: LabeledCounter = LabeledCounter::new();
+pub static HTTP_LATENCY: LabeledHistogram = LabeledHistogram::new();
+pub static API_ERRORS: LabeledCounter = LabeledCounter::new();
+pub static DB_QUERY_LATENCY: SimpleHistogram = SimpleHistogram::new();
+pub static REDIS_LATENCY: SimpleHistogram = SimpleHistogram::new();
+pub static POSTS_CREATED: AtomicU64 = AtomicU64::new(0);
+pub static COMMENTS_CREATED: AtomicU64 = AtomicU64::new(0);
+pub static JOB_RUNS: LabeledCounter = LabeledCounter::new();
+
+fn render_all() -> String {
+    let mut out = String::new();
+
+    out.push_str("# TYPE hole_http_requests_total counter\n");
+    HTTP_REQUESTS.render(&mut out, "hole_http_requests_total");
+    out.push_str("# TYPE hole_http_request_duration_seconds histogram\n");
+    HTTP_LATENCY.render(&mut out, "hole_http_request_duration_seconds");
+    out.push_str("# TYPE hole_api_errors_total counter\n");
+    API_ERRORS.render(&mut out, "hole_api_errors_total");
+    out.push_str("# TYPE hole_db_query_duration_seconds histogram\n");
+    DB_QUERY_LATENCY.render(&mut out, "hole_db_query_duration_seconds");
+    out.push_str("# TYPE hole_redis_command_duration_seconds histogram\n");
+    REDIS_LATENCY.render(&mut out, "hole_redis_command_duration_seconds");
+
+    let _ = writeln!(
+        out,
+        "# TYPE hole_posts_created_total counter\nhole_posts_created_total {}",
+        POSTS_CREATED.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE hole_comments_created_total counter\nhole_comments_created_total {}",
+        COMMENTS_CREATED.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# TYPE hole_job_runs_total counter\n");
+    JOB_RUNS.render(&mut out, "hole_job_runs_total");
+
+    let caches = cache_metrics();
+    let cache_counters: [(&str, fn(&CacheMetricsSnapshot) -> u64); 4] = [
+        ("hits", |m| m.hits),
+        ("misses", |m| m.misses),
+        ("fills", |m| m.fills),
+        ("evictions", |m| m.evictions),
+    ];
+    for (metric, get) in cache_counters {
+        let _ = writeln!(out, "# TYPE hole_cache_{}_total counter", metric);
+        for m in caches.iter() {
+            let _ = writeln!(
+                out,
+                "hole_cache_{}_total{{cache=\"{}\"}} {}",
+                metric,
+                m.name,
+                get(m)
+            );
+        }
+    }
+
+    out
+}
+
+struct RequestStart(Instant);
+
+pub struct Metrics;
+
+#[rocket::async_trait]
+impl Fairing for Metrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Collect request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let elapsed = request
+            .local_cache(|| RequestStart(Instant::now()))
+            .0
+            .elapsed();
+        let route = request.route().map_or("unmatched", |r| r.uri.path());
+        let labels = format!("method=\"{}\",route=\"{}\"", request.method(), route);
+
+        HTTP_REQUESTS.inc(format!("{},status=\"{}\"", labels, response.status().code));
+        HTTP_LATENCY.observe(labels, elapsed);
+    }
+}
+
+// 设置了 metrics_token 时需要 Authorization: Bearer <token>，
+// 否则只允许 metrics_allow_ips 中的地址访问，默认为本机
+pub struct MetricsAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAuth {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = request.rocket().state::<AppConfig>().unwrap();
+        let allowed = match &config.metrics_token {
+            Some(token) => request
+                .headers()
+                .get_one("Authorization")
+                .and_then(|h| h.strip_prefix("Bearer "))
+                .map_or(false, |t| t == token.as_str()),
+            None => request
+                .client_ip()
+                .map_or(false, |ip| config.metrics_allow_ips.contains(&ip)),
+        };
+        if allowed {
+            Outcome::Success(MetricsAuth)
+        } else {
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+#[get("/metrics")]
+pub async fn metrics(_auth: MetricsAuth) -> (ContentType, String) {
+    (ContentType::Plain, render_all())
+}
@@ -0,0 +1,436 @@
+// 从旧版 SQLite 数据库迁移 post, comment, user 三张表，取代 tools/migdb.py
+// 保留原有的 id，按批次在事务中写入，中断后重新运行会从已迁移的位置继续
+use crate::db_conn::Conn;
+use crate::schema::*;
+use chrono::{offset::Utc, DateTime, TimeZone};
+use diesel::dsl::{any, max};
+use diesel::sql_types::{BigInt, Integer, Nullable, Text};
+use diesel::sqlite::SqliteConnection;
+use diesel::{
+    insert_into, sql_query, Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+};
+use std::collections::{BTreeMap, HashMap};
+
+const TMP_PREFIX: &str = "[tmp]\n";
+const SHOW_IDS: usize = 10;
+
+#[derive(QueryableByName)]
+struct OldUser {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "Text"]
+    token: String,
+}
+
+#[derive(QueryableByName)]
+struct OldPost {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    name_hash: String,
+    #[sql_type = "Text"]
+    content: String,
+    #[sql_type = "Nullable<Text>"]
+    cw: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    author_title: Option<String>,
+    #[sql_type = "Integer"]
+    likenum: i32,
+    #[sql_type = "Integer"]
+    n_comments: i32,
+    #[sql_type = "BigInt"]
+    timestamp: i64,
+    #[sql_type = "Nullable<BigInt>"]
+    comment_timestamp: Option<i64>,
+    #[sql_type = "Nullable<Integer>"]
+    deleted: Option<i32>,
+    #[sql_type = "Nullable<Integer>"]
+    is_reported: Option<i32>,
+    #[sql_type = "Integer"]
+    hot_score: i32,
+    #[sql_type = "Nullable<Integer>"]
+    allow_search: Option<i32>,
+}
+
+#[derive(QueryableByName)]
+struct OldComment {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    name_hash: String,
+    #[sql_type = "Nullable<Text>"]
+    author_title: Option<String>,
+    #[sql_type = "Text"]
+    content: String,
+    #[sql_type = "BigInt"]
+    timestamp: i64,
+    #[sql_type = "Nullable<Integer>"]
+    deleted: Option<i32>,
+    #[sql_type = "Integer"]
+    post_id: i32,
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[sql_type = "BigInt"]
+    n: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "posts"]
+struct MigPost {
+    id: i32,
+    author_hash: String,
+    content: String,
+    cw: String,
+    author_title: String,
+    is_tmp: bool,
+    n_attentions: i32,
+    n_comments: i32,
+    create_time: DateTime<Utc>,
+    last_comment_time: DateTime<Utc>,
+    is_deleted: bool,
+    is_reported: bool,
+    hot_score: i32,
+    allow_search: bool,
+}
+
+#[derive(Insertable)]
+#[table_name = "comments"]
+struct MigComment {
+    id: i32,
+    author_hash: String,
+    author_title: String,
+    is_tmp: bool,
+    content: String,
+    create_time: DateTime<Utc>,
+    is_deleted: bool,
+    allow_search: bool,
+    post_id: i32,
+}
+
+// 修补过的行: 说明 -> id
+#[derive(Default)]
+struct Patches(BTreeMap<&'static str, Vec<i32>>);
+
+impl Patches {
+    fn add(&mut self, what: &'static str, id: i32) {
+        self.0.entry(what).or_default().push(id);
+    }
+
+    fn print(&self) {
+        if self.0.is_empty() {
+            println!("没有需要修补的行");
+        }
+        for (what, ids) in self.0.iter() {
+            let shown: Vec<String> = ids.iter().take(SHOW_IDS).map(i32::to_string).collect();
+            println!(
+                "{}: {} 行 ({}{})",
+                what,
+                ids.len(),
+                shown.join(", "),
+                if ids.len() > SHOW_IDS { ", ..." } else { "" }
+            );
+        }
+    }
+}
+
+fn to_time(ts: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(ts, 0).single().unwrap_or_else(Utc::now)
+}
+
+fn count(old: &SqliteConnection, query: &str) -> QueryResult<i64> {
+    Ok(sql_query(query).get_result::<Count>(old)?.n)
+}
+
+fn migrate_users(
+    old: &SqliteConnection,
+    pg: &Conn,
+    batch: i64,
+    patches: &mut Patches,
+) -> QueryResult<()> {
+    let mut last = 0;
+    loop {
+        let rs: Vec<OldUser> = sql_query(
+            "SELECT rowid AS id, name, token FROM user WHERE rowid > ? ORDER BY rowid LIMIT ?",
+        )
+        .bind::<Integer, _>(last)
+        .bind::<BigInt, _>(batch)
+        .load(old)?;
+        let r = match rs.last() {
+            Some(r) => r.id,
+            None => break,
+        };
+
+        pg.transaction::<_, diesel::result::Error, _>(|| {
+            for u in rs.iter() {
+                let n = insert_into(users::table)
+                    .values((users::name.eq(&u.name), users::token.eq(&u.token)))
+                    .on_conflict_do_nothing()
+                    .execute(pg)?;
+                if n == 0 {
+                    patches.add("user: name 或 token 已存在(可能之前已迁移)，跳过", u.id);
+                }
+            }
+            Ok(())
+        })?;
+        println!("users: {}", r);
+        last = r;
+    }
+    Ok(())
+}
+
+// 返回旧数据库中最大的 pid
+fn migrate_posts(
+    old: &SqliteConnection,
+    pg: &Conn,
+    batch: i64,
+    patches: &mut Patches,
+) -> QueryResult<i32> {
+    let old_max = count(old, "SELECT COALESCE(MAX(id), 0) AS n FROM post")? as i32;
+    let mut last: i32 = posts::table
+        .filter(posts::id.le(old_max))
+        .select(max(posts::id))
+        .first::<Option<i32>>(pg)?
+        .unwrap_or(0);
+    if last > 0 {
+        println!("posts: 从 #{} 之后继续", last);
+    }
+
+    loop {
+        let rs: Vec<OldPost> = sql_query(
+            "SELECT id, name_hash, content, cw, author_title, likenum, n_comments, timestamp, \
+             comment_timestamp, deleted, is_reported, hot_score, allow_search \
+             FROM post WHERE id > ? ORDER BY id LIMIT ?",
+        )
+        .bind::<Integer, _>(last)
+        .bind::<BigInt, _>(batch)
+        .load(old)?;
+        let r = match rs.last() {
+            Some(r) => r.id,
+            None => break,
+        };
+
+        let mut ps: Vec<MigPost> = vec![];
+        let mut next_id = last + 1;
+        for p in rs {
+            // 缺失的 id 用已删除的空洞补上，保证洞号不变
+            let now = Utc::now();
+            for id in next_id..p.id {
+                patches.add("post: 缺失的 id，补为已删除的空洞", id);
+                ps.push(MigPost {
+                    id,
+                    author_hash: String::new(),
+                    content: String::new(),
+                    cw: String::new(),
+                    author_title: String::new(),
+                    is_tmp: false,
+                    n_attentions: 0,
+                    n_comments: 0,
+                    create_time: now,
+                    last_comment_time: now,
+                    is_deleted: true,
+                    is_reported: false,
+                    hot_score: 0,
+                    allow_search: false,
+                });
+            }
+            next_id = p.id + 1;
+
+            if p.comment_timestamp.is_none() {
+                patches.add("post: 没有最后评论时间，使用发布时间", p.id);
+            }
+            ps.push(MigPost {
+                id: p.id,
+                author_hash: p.name_hash,
+                is_tmp: p.content.starts_with(TMP_PREFIX),
+                content: p.content,
+                cw: p.cw.unwrap_or_default(),
+                author_title: p.author_title.unwrap_or_default(),
+                n_attentions: p.likenum,
+                n_comments: p.n_comments,
+                create_time: to_time(p.timestamp),
+                last_comment_time: to_time(p.comment_timestamp.unwrap_or(p.timestamp)),
+                is_deleted: p.deleted.unwrap_or(0) != 0,
+                is_reported: p.is_reported.unwrap_or(0) != 0,
+                hot_score: p.hot_score,
+                allow_search: p.allow_search.unwrap_or(0) != 0,
+            });
+        }
+
+        // 补上的空洞可能很多，分开写入以免超出参数个数限制
+        pg.transaction::<_, diesel::result::Error, _>(|| {
+            for chunk in ps.chunks(batch as usize) {
+                insert_into(posts::table)
+                    .values(chunk)
+                    .on_conflict(posts::id)
+                    .do_nothing()
+                    .execute(pg)?;
+            }
+            Ok(())
+        })?;
+        println!("posts: #{}", r);
+        last = r;
+    }
+
+    Ok(old_max)
+}
+
+fn migrate_comments(
+    old: &SqliteConnection,
+    pg: &Conn,
+    batch: i64,
+    patches: &mut Patches,
+) -> QueryResult<()> {
+    let old_max = count(old, "SELECT COALESCE(MAX(id), 0) AS n FROM comment")? as i32;
+    let mut last: i32 = comments::table
+        .filter(comments::id.le(old_max))
+        .select(max(comments::id))
+        .first::<Option<i32>>(pg)?
+        .unwrap_or(0);
+    if last > 0 {
+        println!("comments: 从 {} 之后继续", last);
+    }
+
+    loop {
+        let rs: Vec<OldComment> = sql_query(
+            "SELECT id, name_hash, author_title, content, timestamp, deleted, post_id \
+             FROM comment WHERE id > ? ORDER BY id LIMIT ?",
+        )
+        .bind::<Integer, _>(last)
+        .bind::<BigInt, _>(batch)
+        .load(old)?;
+        let r = match rs.last() {
+            Some(r) => r.id,
+            None => break,
+        };
+
+        // 评论是否可搜索与所属的洞相同
+        let pids: Vec<i32> = rs.iter().map(|c| c.post_id).collect();
+        let searchable: HashMap<i32, bool> = posts::table
+            .filter(posts::id.eq(any(pids)))
+            .select((posts::id, posts::allow_search))
+            .load::<(i32, bool)>(pg)?
+            .into_iter()
+            .collect();
+
+        let mut cs: Vec<MigComment> = vec![];
+        for c in rs {
+            let allow_search = match searchable.get(&c.post_id) {
+                Some(s) => *s,
+                None => {
+                    patches.add("comment: 所属的洞不存在，跳过", c.id);
+                    continue;
+                }
+            };
+            cs.push(MigComment {
+                id: c.id,
+                author_hash: c.name_hash,
+                author_title: c.author_title.unwrap_or_default(),
+                is_tmp: c.content.starts_with(TMP_PREFIX),
+                content: c.content,
+                create_time: to_time(c.timestamp),
+                is_deleted: c.deleted.unwrap_or(0) != 0,
+                allow_search,
+                post_id: c.post_id,
+            });
+        }
+
+        pg.transaction::<_, diesel::result::Error, _>(|| {
+            insert_into(comments::table)
+                .values(&cs)
+                .on_conflict(comments::id)
+                .do_nothing()
+                .execute(pg)
+        })?;
+        println!("comments: {}", r);
+        last = r;
+    }
+
+    Ok(())
+}
+
+// 使用了指定的 id，需要更新自增序列
+fn reset_sequences(pg: &Conn) -> QueryResult<()> {
+    for (table, seq) in [("posts", "posts_id_seq"), ("comments", "comments_id_seq")] {
+        sql_query(format!(
+            "SELECT setval('{}', (SELECT COALESCE(MAX(id), 1) FROM {}))",
+            seq, table
+        ))
+        .execute(pg)?;
+    }
+    Ok(())
+}
+
+fn verify(old: &SqliteConnection, pg: &Conn, max_pid: i32) -> QueryResult<Vec<String>> {
+    let mut errors = vec![];
+
+    // 洞号连续，每个 id 都应该存在
+    let n_posts: i64 = posts::table
+        .filter(posts::id.le(max_pid))
+        .count()
+        .get_result(pg)?;
+    let old_posts = count(old, "SELECT COUNT(*) AS n FROM post")?;
+    println!("post: 旧 {}，新 {} (含补上的空洞)", old_posts, n_posts);
+    if n_posts != i64::from(max_pid) {
+        errors.push(format!("posts: 应有 {} 行，实际 {} 行", max_pid, n_posts));
+    }
+
+    let old_max_cid = count(old, "SELECT COALESCE(MAX(id), 0) AS n FROM comment")? as i32;
+    let n_comments: i64 = comments::table
+        .filter(comments::id.le(old_max_cid))
+        .count()
+        .get_result(pg)?;
+    let old_comments = count(old, "SELECT COUNT(*) AS n FROM comment")?;
+    let orphans = count(
+        old,
+        &format!(
+            "SELECT COUNT(*) AS n FROM comment WHERE post_id < 1 OR post_id > {}",
+            max_pid
+        ),
+    )?;
+    println!(
+        "comment: 旧 {}，新 {}，无法迁移 {}",
+        old_comments, n_comments, orphans
+    );
+    if n_comments != old_comments - orphans {
+        errors.push(format!(
+            "comments: 应有 {} 行，实际 {} 行",
+            old_comments - orphans,
+            n_comments
+        ));
+    }
+
+    let old_users = count(old, "SELECT COUNT(*) AS n FROM user")?;
+    let n_users: i64 = users::table.count().get_result(pg)?;
+    println!("user: 旧 {}，新 {}", old_users, n_users);
+    if n_users < old_users {
+        errors.push(format!(
+            "users: 至少应有 {} 行，实际 {} 行",
+            old_users, n_users
+        ));
+    }
+
+    Ok(errors)
+}
+
+pub fn run(sqlite_path: &str, pg: &Conn, batch: i64) -> Result<(), String> {
+    let old = SqliteConnection::establish(sqlite_path).map_err(|e| e.to_string())?;
+    let mut patches = Patches::default();
+
+    migrate_users(&old, pg, batch, &mut patches).map_err(|e| e.to_string())?;
+    let max_pid = migrate_posts(&old, pg, batch, &mut patches).map_err(|e| e.to_string())?;
+    migrate_comments(&old, pg, batch, &mut patches).map_err(|e| e.to_string())?;
+    reset_sequences(pg).map_err(|e| e.to_string())?;
+
+    patches.print();
+    let errors = verify(&old, pg, max_pid).map_err(|e| e.to_string())?;
+    if errors.is_empty() {
+        println!("迁移完成");
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
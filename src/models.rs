@@ -1,21 +1,28 @@
 #![allow(clippy::all)]
 
 use crate::cache::*;
-use crate::db_conn::{Conn, Db};
+use crate::config::{AppConfig, RoomSeed};
+use crate::db_conn::{Conn, Db, JobConn};
+use crate::metrics::{COMMENTS_CREATED, POSTS_CREATED};
 use crate::random_hasher::random_string;
 use crate::rds_conn::RdsConn;
+use crate::rds_models::{LegacyPollOption, LegacyPollVote};
 use crate::schema::*;
 use chrono::{offset::Utc, DateTime};
-use diesel::dsl::any;
+use diesel::dsl::{any, sql};
 use diesel::sql_types::*;
 use diesel::{
-    insert_into, BoolExpressionMethods, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
-    TextExpressionMethods,
+    insert_into, BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl,
+    QueryResult, RunQueryDsl, TextExpressionMethods,
 };
 use rocket::futures::{future, join};
 use rocket::serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
 
 no_arg_sql_function!(RANDOM, (), "Represents the sql RANDOM() function");
 sql_function!(fn floor(x: Float) -> Int4);
@@ -84,6 +91,24 @@ macro_rules! base_query {
     };
 }
 
+// 列表中不包含定时发布的洞，按热度等排序时不包含被举报的，按最后回复排序时只包含有回复的
+macro_rules! list_filter {
+    ($query:ident, $room_id:expr, $order_mode:expr) => {
+        $query = $query.filter(posts::is_scheduled.eq(false));
+        if $order_mode > 0 {
+            $query = $query.filter(posts::is_reported.eq(false));
+        }
+
+        if $order_mode == 1 {
+            $query = $query.filter(posts::n_comments.gt(0));
+        }
+
+        if let Some(ri) = $room_id {
+            $query = $query.filter(posts::room_id.eq(ri));
+        }
+    };
+}
+
 macro_rules! with_log {
     ($c: expr) => {{
         use crate::libs::diesel_logger::LoggingConnection;
@@ -105,6 +130,127 @@ pub struct Comment {
     pub post_id: i32,
 }
 
+// 时间衰减热度: ln(1 + 加权互动数) + 发帖时间 / tau
+// 所有洞以相同速率衰减，排名只在有新互动时变化，不需要定时刷新缓存
+#[derive(Clone, Copy, Debug)]
+pub struct HotDecay {
+    pub tau: f64,
+    pub w_attention: f64,
+    pub w_comment: f64,
+    pub w_reaction: f64,
+}
+
+// 启动时由配置设置
+static HOT_DECAY: Mutex<Option<HotDecay>> = Mutex::new(None);
+
+impl HotDecay {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            // 半衰期，单位为小时
+            tau: config.hot_decay_half_life * 3600.0 / std::f64::consts::LN_2,
+            w_attention: config.hot_weight_attention,
+            w_comment: config.hot_weight_comment,
+            w_reaction: config.hot_weight_reaction,
+        }
+    }
+
+    pub fn set(decay: Self) {
+        *HOT_DECAY.lock().unwrap() = Some(decay);
+    }
+
+    pub fn current() -> Self {
+        HOT_DECAY.lock().unwrap().unwrap_or(Self {
+            tau: 24.0 * 3600.0 / std::f64::consts::LN_2,
+            w_attention: 1.0,
+            w_comment: 1.0,
+            w_reaction: 1.0,
+        })
+    }
+
+    pub fn score(&self, p: &Post) -> f64 {
+        let points = self.w_attention * f64::from(p.n_attentions)
+            + self.w_comment * f64::from(p.n_comments)
+            + self.w_reaction * f64::from(p.up_votes - p.down_votes);
+        points.max(0.0).ln_1p() + p.create_time.timestamp() as f64 / self.tau
+    }
+
+    // 参数不同时排序值不可比较，用于让旧参数下生成的游标失效
+    pub fn fingerprint(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        for v in [self.tau, self.w_attention, self.w_comment, self.w_reaction] {
+            v.to_bits().hash(&mut h);
+        }
+        h.finish()
+    }
+
+    pub fn sql(&self) -> String {
+        format!(
+            "ln(1 + greatest({} * n_attentions + {} * n_comments + {} * (up_votes - down_votes), 0)) \
+             + floor(extract(epoch from create_time)) / {}",
+            self.w_attention, self.w_comment, self.w_reaction, self.tau
+        )
+    }
+}
+
+// 列表的游标，记录排序方式、已翻过的页数和上一页最后一条的排序值与 pid
+// 热度排序(mode 5)还记录热度参数的指纹，参数改变后旧游标无效，其它排序为 0
+// 编码后对前端不透明，只能原样传回
+#[derive(Clone, Copy, Debug)]
+pub struct ListCursor {
+    pub mode: u8,
+    pub page: u32,
+    pub last: Option<(i64, i32)>,
+    pub decay: u64,
+}
+
+impl ListCursor {
+    fn decay_of(mode: u8) -> u64 {
+        if mode == 5 {
+            HotDecay::current().fingerprint()
+        } else {
+            0
+        }
+    }
+
+    pub fn start(mode: u8) -> Self {
+        Self {
+            mode,
+            page: 0,
+            last: None,
+            decay: Self::decay_of(mode),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let (key, pid) = self.last.unwrap_or_default();
+        format!("{}.{}.{}.{}.{}", self.mode, self.page, key, pid, self.decay)
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    // 随机模式没有固定的顺序，不支持游标；排序方式与请求不一致或热度参数已改变时也视为无效
+    pub fn decode(s: &str, mode: u8) -> Option<Self> {
+        if mode == 3 || s.len() % 2 != 0 {
+            return None;
+        }
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let text = String::from_utf8(bytes).ok()?;
+        let mut parts = text.split('.');
+        let cursor = Self {
+            mode: parts.next()?.parse().ok()?,
+            page: parts.next()?.parse().ok()?,
+            last: Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?)),
+            decay: parts.next()?.parse().ok()?,
+        };
+        (parts.next().is_none() && cursor.mode == mode && cursor.decay == Self::decay_of(mode))
+            .then_some(cursor)
+    }
+}
+
 #[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct Post {
@@ -125,6 +271,9 @@ pub struct Post {
     pub room_id: i32,
     pub up_votes: i32,
     pub down_votes: i32,
+    pub is_locked: bool,
+    pub expire_time: Option<DateTime<Utc>>,
+    pub is_scheduled: bool, // 定时发布，到create_time时才可见
 }
 
 #[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
@@ -147,6 +296,9 @@ pub struct NewPost {
     pub n_attentions: i32,
     pub allow_search: bool,
     pub room_id: i32,
+    pub expire_time: Option<DateTime<Utc>>,
+    pub create_time: Option<DateTime<Utc>>,
+    pub is_scheduled: bool,
 }
 
 impl Post {
@@ -188,7 +340,7 @@ impl Post {
         // dbg!(&cached_posts);
         Ok(cached_posts
             .into_iter()
-            .filter_map(|p| p.filter(|p| !p.is_deleted))
+            .filter_map(|p| p.filter(|p| !p.is_deleted && !p.is_scheduled))
             .collect())
     }
 
@@ -235,14 +387,105 @@ impl Post {
             let ps = Self::get_multi(db, rconn, &pids).await?;
             cacher.fill(&ps).await;
         }
-        let pids = if start + limit > cacher.i64_len() {
-            Self::_get_ids_by_page(db, room_id, order_mode, start, limit).await?
-        } else {
-            cacher.get_pids(start, limit).await
+        let pids = match cacher.get_pids(start, limit).await {
+            Some(pids) => pids,
+            None => Self::_get_ids_by_page(db, room_id, order_mode, start, limit).await?,
         };
 
         Self::get_multi(db, rconn, &pids).await
     }
+    // 按游标翻页：取 last 之后的 limit 条，同时返回每条的排序值
+    // 优先从缓存中取，缓存不够时查数据库
+    pub async fn gets_after(
+        db: &Db,
+        rconn: &RdsConn,
+        room_id: Option<i32>,
+        cursor: &ListCursor,
+        limit: usize,
+    ) -> QueryResult<(Vec<Self>, Option<ListCursor>)> {
+        let mut cacher = PostListCache::init(room_id, cursor.mode, &rconn);
+        if cacher.need_fill().await {
+            let pids =
+                Self::_get_ids_by_page(db, room_id, cursor.mode, 0, cacher.i64_minlen()).await?;
+            let ps = Self::get_multi(db, rconn, &pids).await?;
+            cacher.fill(&ps).await;
+        }
+        let keys = match cacher.get_after(cursor.last, limit).await {
+            Some(keys) => keys,
+            None => {
+                Self::_get_keys_after(db, room_id, cursor.mode, cursor.last, limit as i64).await?
+            }
+        };
+
+        // 不足一页说明已经到底
+        let next = keys
+            .last()
+            .copied()
+            .filter(|_| keys.len() >= limit)
+            .map(|last| ListCursor {
+                mode: cursor.mode,
+                page: cursor.page + 1,
+                last: Some(last),
+                decay: cursor.decay,
+            });
+        let pids = keys.into_iter().map(|(_, pid)| pid).collect();
+        Ok((Self::get_multi(db, rconn, &pids).await?, next))
+    }
+
+    // 列表中的排序值，越小越靠前，与 PostListCache 中的 score 一致
+    // 随机模式没有固定的顺序，返回 None
+    pub fn list_key(&self, mode: u8, decay: Option<HotDecay>) -> Option<i64> {
+        match mode {
+            // 定时洞发布后id不变，按发布时间排序
+            0 => Some(-self.create_time.timestamp()),
+            1 => Some(-self.last_comment_time.timestamp()),
+            2 => Some((-self.hot_score).into()),
+            4 => Some((-self.n_attentions).into()),
+            5 => Some((-decay.unwrap_or_else(HotDecay::current).score(self) * 1e6) as i64),
+            _ => None,
+        }
+    }
+
+    // 与 list_key 相同的排序值的 SQL 表达式
+    fn list_key_sql(mode: u8) -> String {
+        match mode {
+            0 => "(-floor(extract(epoch from create_time))::bigint)".to_owned(),
+            1 => "(-floor(extract(epoch from last_comment_time))::bigint)".to_owned(),
+            2 => "(-hot_score)::bigint".to_owned(),
+            4 => "(-n_attentions)::bigint".to_owned(),
+            5 => format!("trunc(-({}) * 1000000)::bigint", HotDecay::current().sql()),
+            _ => panic!("Wrong order mode!"),
+        }
+    }
+
+    async fn _get_keys_after(
+        db: &Db,
+        room_id: Option<i32>,
+        order_mode: u8,
+        last: Option<(i64, i32)>,
+        limit: i64,
+    ) -> QueryResult<Vec<(i64, i32)>> {
+        let key = Self::list_key_sql(order_mode);
+        db.run(move |c| {
+            let mut query = base_query!(posts).select((sql::<BigInt>(&key), posts::id));
+            list_filter!(query, room_id, order_mode);
+
+            // 同一排序值内按 pid 从大到小
+            if let Some((k, pid)) = last {
+                query = query.filter(sql::<Bool>(&format!(
+                    "({0} > {1} OR ({0} = {1} AND id < {2}))",
+                    key, k, pid
+                )));
+            }
+            query = query
+                .order(sql::<BigInt>(&key).asc())
+                .then_order_by(posts::id.desc());
+
+            query.limit(limit).load(with_log!(c))
+        })
+        .await
+    }
+
     async fn _get_ids_by_page(
         db: &Db,
         room_id: Option<i32>,
@@ -252,24 +495,17 @@ impl Post {
     ) -> QueryResult<Vec<i32>> {
         db.run(move |c| {
             let mut query = base_query!(posts).select(posts::id);
-            if order_mode > 0 {
-                query = query.filter(posts::is_reported.eq(false));
-            }
-
-            if order_mode == 1 {
-                query = query.filter(posts::n_comments.gt(0));
-            }
-
-            if let Some(ri) = room_id {
-                query = query.filter(posts::room_id.eq(ri));
-            }
+            list_filter!(query, room_id, order_mode);
 
             query = match order_mode {
-                0 => query.order(posts::id.desc()),
+                0 => query
+                    .order(posts::create_time.desc())
+                    .then_order_by(posts::id.desc()),
                 1 => query.order(posts::last_comment_time.desc()),
                 2 => query.order(posts::hot_score.desc()),
                 3 => query.order(RANDOM),
                 4 => query.order(posts::n_attentions.desc()),
+                5 => query.order(sql::<Double>(&HotDecay::current().sql()).desc()),
                 _ => panic!("Wrong order mode!"),
             };
 
@@ -295,7 +531,8 @@ impl Post {
                     .select(posts::id)
                     .distinct()
                     .left_join(comments::table)
-                    .filter(posts::is_reported.eq(false));
+                    .filter(posts::is_reported.eq(false))
+                    .filter(posts::is_scheduled.eq(false));
                 if let Some(ri) = room_id {
                     query = query.filter(posts::room_id.eq(ri));
                 }
@@ -342,12 +579,15 @@ impl Post {
     }
 
     pub async fn create(db: &Db, new_post: NewPost) -> QueryResult<Self> {
-        db.run(move |c| {
-            insert_into(posts::table)
-                .values(&new_post)
-                .get_result(with_log!(c))
-        })
-        .await
+        let p = db
+            .run(move |c| {
+                insert_into(posts::table)
+                    .values(&new_post)
+                    .get_result(with_log!(c))
+            })
+            .await?;
+        POSTS_CREATED.fetch_add(1, Ordering::Relaxed);
+        Ok(p)
     }
 
     pub async fn set_instance_cache(&self, rconn: &RdsConn) {
@@ -356,30 +596,133 @@ impl Post {
     pub async fn refresh_cache(&self, rconn: &RdsConn, is_new: bool) {
         join!(
             self.set_instance_cache(rconn),
-            future::join_all((if is_new { [0, 2, 3, 4] } else { [1, 2, 3, 4] }).map(
-                |mode| async move {
+            future::join_all(
+                (if is_new {
+                    [0, 2, 3, 4, 5]
+                } else {
+                    [1, 2, 3, 4, 5]
+                })
+                .map(|mode| async move {
                     PostListCache::init(None, mode, &rconn.clone())
                         .put(self)
                         .await;
                     PostListCache::init(Some(self.room_id), mode, &rconn.clone())
                         .put(self)
                         .await;
-                }
-            )),
+                })
+            ),
         );
     }
 
-    pub async fn annealing(c: &mut Conn, rconn: &mut RdsConn) {
+    pub async fn annealing(c: &mut JobConn, rconn: &mut RdsConn) -> QueryResult<()> {
         info!("Time for annealing!");
-        diesel::update(posts::table.filter(posts::hot_score.gt(10)))
-            .set(posts::hot_score.eq(floor(float4(posts::hot_score) * 0.9)))
-            .execute(with_log!(c))
-            .unwrap();
+        c.run(|c| {
+            diesel::update(posts::table.filter(posts::hot_score.gt(10)))
+                .set(posts::hot_score.eq(floor(float4(posts::hot_score) * 0.9)))
+                .execute(with_log!(c))
+        })
+        .await?;
 
         PostCache::clear_all(rconn).await;
-        for room_id in (0..5).map(Some).chain([None, Some(42)]) {
+        let room_ids = c
+            .run(Room::all_sync)
+            .await?
+            .into_iter()
+            .map(|r| Some(r.id))
+            .chain([None]);
+        for room_id in room_ids {
             PostListCache::init(room_id, 2, rconn).clear().await;
         }
+        Ok(())
+    }
+
+    pub async fn gets_scheduled_by_author(db: &Db, author_hash: &str) -> QueryResult<Vec<Self>> {
+        let author_hash = author_hash.to_string();
+        db.run(move |c| {
+            base_query!(posts)
+                .filter(posts::is_scheduled.eq(true))
+                .filter(posts::author_hash.eq(author_hash))
+                .order(posts::create_time)
+                .load(with_log!(c))
+        })
+        .await
+    }
+
+    // 发布到时间的定时洞
+    pub async fn publish_scheduled(c: &mut JobConn, rconn: &RdsConn) -> QueryResult<()> {
+        let ps: Vec<Self> = c
+            .run(|c| {
+                diesel::update(
+                    posts::table
+                        .filter(posts::is_deleted.eq(false))
+                        .filter(posts::is_scheduled.eq(true))
+                        .filter(posts::create_time.le(Utc::now())),
+                )
+                .set((
+                    posts::is_scheduled.eq(false),
+                    posts::last_comment_time.eq(posts::create_time),
+                ))
+                .get_results(with_log!(c))
+            })
+            .await?;
+
+        for p in ps.iter() {
+            p.refresh_cache(rconn, true).await;
+        }
+        if !ps.is_empty() {
+            info!("{} scheduled posts published", ps.len());
+        }
+        Ok(())
+    }
+
+    // 自毁时间到了的洞与其评论一起删除
+    pub async fn clear_expired(c: &mut JobConn, rconn: &RdsConn) -> QueryResult<()> {
+        let ps: Vec<Self> = c
+            .run(|c| {
+                diesel::update(
+                    posts::table
+                        .filter(posts::is_deleted.eq(false))
+                        .filter(posts::expire_time.le(Utc::now())),
+                )
+                .set(posts::is_deleted.eq(true))
+                .get_results(with_log!(c))
+            })
+            .await?;
+        if ps.is_empty() {
+            return Ok(());
+        }
+
+        let pids: Vec<i32> = ps.iter().map(|p| p.id).collect();
+        c.run(move |c| {
+            diesel::update(comments::table.filter(comments::post_id.eq(any(pids))))
+                .set(comments::is_deleted.eq(true))
+                .execute(with_log!(c))
+        })
+        .await?;
+
+        for p in ps.iter() {
+            p.refresh_cache(rconn, true).await;
+            p.clear_comments_cache(rconn).await;
+        }
+        info!("{} expired posts deleted", ps.len());
+        Ok(())
+    }
+
+    // 被举报后隐藏、等待处理的洞，只用于命令行
+    pub fn get_reported_sync(c: &mut Conn) -> QueryResult<Vec<Self>> {
+        posts::table
+            .filter(posts::is_reported.eq(true))
+            .filter(posts::is_deleted.eq(false))
+            .order(posts::id.desc())
+            .load(with_log!(c))
+    }
+
+    // tag 通过 cw 和全文搜索实现，重建对应的索引
+    pub fn reindex_tags_sync(c: &mut Conn) -> QueryResult<()> {
+        for idx in ["posts_cw_idx", "posts_search_text_trgm_idx"] {
+            diesel::sql_query(format!("REINDEX INDEX {}", idx)).execute(with_log!(c))?;
+        }
+        Ok(())
     }
 }
 
@@ -451,6 +794,21 @@ impl User {
         .await
     }
 
+    // 已存在时设为管理员，返回 token，只用于命令行
+    pub fn set_admin_sync(c: &mut Conn, name: &str) -> QueryResult<String> {
+        diesel::insert_into(users::table)
+            .values((
+                users::name.eq(name),
+                users::token.eq(random_string(16)),
+                users::is_admin.eq(true),
+            ))
+            .on_conflict(users::name)
+            .do_update()
+            .set(users::is_admin.eq(true))
+            .returning(users::token)
+            .get_result(with_log!(c))
+    }
+
     pub async fn clear_non_admin_users(c: &mut Conn, rconn: &mut RdsConn) {
         diesel::delete(users::table.filter(users::is_admin.eq(false)))
             .execute(c)
@@ -483,12 +841,15 @@ impl Comment {
     }
 
     pub async fn create(db: &Db, new_comment: NewComment) -> QueryResult<Self> {
-        db.run(move |c| {
-            insert_into(comments::table)
-                .values(&new_comment)
-                .get_result(with_log!(c))
-        })
-        .await
+        let c = db
+            .run(move |c| {
+                insert_into(comments::table)
+                    .values(&new_comment)
+                    .get_result(with_log!(c))
+            })
+            .await?;
+        COMMENTS_CREATED.fetch_add(1, Ordering::Relaxed);
+        Ok(c)
     }
 
     pub async fn gets_by_post_id(db: &Db, post_id: i32) -> QueryResult<Vec<Self>> {
@@ -503,4 +864,547 @@ impl Comment {
     }
 }
 
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PollOption {
+    pub id: i32,
+    pub poll_id: i32,
+    pub idx: i32,
+    pub content: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "poll_options"]
+pub struct NewPollOption {
+    pub poll_id: i32,
+    pub idx: i32,
+    pub content: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "poll_votes"]
+pub struct NewPollVote {
+    pub poll_id: i32,
+    pub voter_hash: String,
+    pub option_id: i32,
+}
+
+// 一个洞的投票及其统计结果，整体缓存
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PollData {
+    pub post_id: i32,
+    pub poll_id: i32,
+    pub options: Vec<PollOption>,
+    pub votes: Vec<i64>,
+    pub voters: HashMap<String, usize>, // namehash -> index of options
+}
+
+impl PollData {
+    fn new(post_id: i32, poll_id: i32, options: Vec<PollOption>, vs: Vec<(String, i32)>) -> Self {
+        let mut votes = vec![0; options.len()];
+        let voters = vs
+            .into_iter()
+            .filter_map(|(hash, option_id)| {
+                let idx = options.iter().position(|opt| opt.id == option_id)?;
+                votes[idx] += 1;
+                Some((hash, idx))
+            })
+            .collect();
+        Self {
+            post_id,
+            poll_id,
+            options,
+            votes,
+            voters,
+        }
+    }
+
+    // return false if already voted
+    pub async fn add_vote(
+        &self,
+        db: &Db,
+        rconn: &RdsConn,
+        idx: usize,
+        namehash: &str,
+    ) -> QueryResult<bool> {
+        let new_vote = NewPollVote {
+            poll_id: self.poll_id,
+            voter_hash: namehash.to_string(),
+            option_id: self.options[idx].id,
+        };
+        let n = db
+            .run(move |c| {
+                insert_into(poll_votes::table)
+                    .values(&new_vote)
+                    .on_conflict_do_nothing()
+                    .execute(with_log!(c))
+            })
+            .await?;
+        PollCache::init(self.post_id, rconn).clear().await;
+        Ok(n > 0)
+    }
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Poll {
+    pub id: i32,
+    pub post_id: i32,
+    pub create_time: DateTime<Utc>,
+}
+
+impl Poll {
+    // options: (选项, 已投票的namehash)
+    fn _create(c: &mut Conn, post_id: i32, options: Vec<(String, Vec<String>)>) -> QueryResult<()> {
+        let conn = with_log!(c);
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let poll_id: i32 = insert_into(polls::table)
+                .values(polls::post_id.eq(post_id))
+                .returning(polls::id)
+                .get_result(conn)?;
+            let (contents, voters): (Vec<String>, Vec<Vec<String>>) = options.into_iter().unzip();
+            let option_ids: Vec<i32> = insert_into(poll_options::table)
+                .values(
+                    &contents
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, content)| NewPollOption {
+                            poll_id,
+                            idx: idx as i32,
+                            content,
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .returning(poll_options::id)
+                .get_results(conn)?;
+            let new_votes = option_ids
+                .into_iter()
+                .zip(voters.into_iter())
+                .flat_map(|(option_id, hashes)| {
+                    hashes.into_iter().map(move |voter_hash| NewPollVote {
+                        poll_id,
+                        voter_hash,
+                        option_id,
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !new_votes.is_empty() {
+                insert_into(poll_votes::table)
+                    .values(&new_votes)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub async fn create(db: &Db, post_id: i32, options: Vec<String>) -> QueryResult<()> {
+        db.run(move |c| {
+            Self::_create(
+                c,
+                post_id,
+                options.into_iter().map(|opt| (opt, vec![])).collect(),
+            )
+        })
+        .await
+    }
+
+    async fn _get_data(db: &Db, post_id: i32) -> QueryResult<Option<PollData>> {
+        db.run(move |c| {
+            let poll_id = match polls::table
+                .filter(polls::post_id.eq(post_id))
+                .select(polls::id)
+                .first::<i32>(with_log!(c))
+                .optional()?
+            {
+                Some(id) => id,
+                None => return Ok(None),
+            };
+            let options = poll_options::table
+                .filter(poll_options::poll_id.eq(poll_id))
+                .order(poll_options::idx)
+                .load(with_log!(c))?;
+            let vs = poll_votes::table
+                .filter(poll_votes::poll_id.eq(poll_id))
+                .select((poll_votes::voter_hash, poll_votes::option_id))
+                .load(with_log!(c))?;
+            Ok(Some(PollData::new(post_id, poll_id, options, vs)))
+        })
+        .await
+    }
+
+    pub async fn get_data(db: &Db, rconn: &RdsConn, post_id: i32) -> QueryResult<Option<PollData>> {
+        let mut cacher = PollCache::init(post_id, rconn);
+        if let Some(pd) = cacher.get().await {
+            Ok(pd)
+        } else {
+            let pd = Self::_get_data(db, post_id).await?;
+            cacher.set(&pd).await;
+            Ok(pd)
+        }
+    }
+
+    // 导入旧版存在redis中的投票，成功后删除redis中的数据
+    // 出错时跳过该投票并保留redis中的数据，下次启动时重试
+    pub async fn import_legacy(c: &mut Conn, rconn: &RdsConn) {
+        let pids = match LegacyPollOption::all_pids(rconn).await {
+            Ok(pids) => pids,
+            Err(e) => {
+                warn!("scan legacy polls failed: {}", e);
+                return;
+            }
+        };
+        for pid in pids {
+            if let Err(e) = Self::_import_legacy_one(c, rconn, pid).await {
+                warn!("import legacy poll of #{} failed: {}", pid, e);
+                continue;
+            }
+            PollCache::init(pid, rconn).clear().await;
+        }
+    }
+
+    async fn _import_legacy_one(
+        c: &mut Conn,
+        rconn: &RdsConn,
+        pid: i32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let opts = LegacyPollOption::init(pid, rconn).get_list().await?;
+        let n_opts = opts.len();
+        let mut options = vec![];
+        for (idx, opt) in opts.into_iter().enumerate() {
+            let hashes = LegacyPollVote::init(pid, idx, rconn).all().await?;
+            options.push((opt, hashes));
+        }
+
+        let exists = polls::table
+            .filter(polls::post_id.eq(pid))
+            .count()
+            .get_result::<i64>(with_log!(c))?
+            > 0;
+        if !exists {
+            Self::_create(c, pid, options)?;
+            info!("legacy poll of #{} imported", pid);
+        }
+
+        LegacyPollOption::init(pid, rconn).clear().await?;
+        for idx in 0..n_opts {
+            LegacyPollVote::init(pid, idx, rconn).clear().await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct EmojiReaction {
+    pub comment_id: i32, // 0 for the post itself
+    pub user_hash: String,
+    pub emoji: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "reactions"]
+pub struct NewEmojiReaction {
+    pub post_id: i32,
+    pub comment_id: i32,
+    pub user_hash: String,
+    pub emoji: String,
+}
+
+impl EmojiReaction {
+    async fn _gets_by_post_id(db: &Db, post_id: i32) -> QueryResult<Vec<Self>> {
+        db.run(move |c| {
+            reactions::table
+                .filter(reactions::post_id.eq(post_id))
+                .select((
+                    reactions::comment_id,
+                    reactions::user_hash,
+                    reactions::emoji,
+                ))
+                .order(reactions::create_time)
+                .load(with_log!(c))
+        })
+        .await
+    }
+
+    // 包括洞本身和其下所有评论的表情
+    pub async fn gets_by_post_id(db: &Db, rconn: &RdsConn, post_id: i32) -> QueryResult<Vec<Self>> {
+        let mut cacher = EmojiReactionCache::init(post_id, rconn);
+        if let Some(rs) = cacher.get().await {
+            Ok(rs)
+        } else {
+            let rs = Self::_gets_by_post_id(db, post_id).await?;
+            cacher.set(&rs).await;
+            Ok(rs)
+        }
+    }
+
+    // return false if nothing changed
+    pub async fn set(
+        db: &Db,
+        rconn: &RdsConn,
+        new_reaction: NewEmojiReaction,
+        switch_to: bool,
+    ) -> QueryResult<bool> {
+        let post_id = new_reaction.post_id;
+        let n = db
+            .run(move |c| {
+                if switch_to {
+                    insert_into(reactions::table)
+                        .values(&new_reaction)
+                        .on_conflict_do_nothing()
+                        .execute(with_log!(c))
+                } else {
+                    diesel::delete(
+                        reactions::table
+                            .filter(reactions::post_id.eq(new_reaction.post_id))
+                            .filter(reactions::comment_id.eq(new_reaction.comment_id))
+                            .filter(reactions::user_hash.eq(&new_reaction.user_hash))
+                            .filter(reactions::emoji.eq(&new_reaction.emoji)),
+                    )
+                    .execute(with_log!(c))
+                }
+            })
+            .await?;
+        if n > 0 {
+            EmojiReactionCache::init(post_id, rconn).clear().await;
+        }
+        Ok(n > 0)
+    }
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Room {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub is_visible: bool,
+    pub admin_only: bool,     // 只有管理员可以发帖
+    pub allow_delete: bool,   // 非管理员能否删除
+    pub allow_tmp: bool,      // 临时用户能否发帖
+    pub accept_reports: bool, // 举报自动代发到这里
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "rooms"]
+pub struct NewRoom {
+    pub name: String,
+    pub description: String,
+    pub is_visible: bool,
+    pub admin_only: bool,
+    pub allow_delete: bool,
+    pub allow_tmp: bool,
+    pub accept_reports: bool,
+}
+
+impl Room {
+    // get sync, only for background tasks
+    pub fn all_sync(c: &mut Conn) -> QueryResult<Vec<Self>> {
+        rooms::table.order(rooms::id).load(with_log!(c))
+    }
+
+    pub async fn all(db: &Db, rconn: &RdsConn) -> QueryResult<Vec<Self>> {
+        let mut cacher = RoomCache::init(rconn);
+        if let Some(rs) = cacher.get().await {
+            Ok(rs)
+        } else {
+            let rs = db.run(move |c| Self::all_sync(c)).await?;
+            cacher.set(&rs).await;
+            Ok(rs)
+        }
+    }
+
+    pub async fn get(db: &Db, rconn: &RdsConn, id: i32) -> QueryResult<Self> {
+        Self::all(db, rconn)
+            .await?
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or(diesel::result::Error::NotFound)
+    }
+
+    // 创建配置中有而数据库中没有的房间，只在启动时调用
+    pub async fn seed(c: &mut Conn, rconn: &RdsConn, seeds: &[RoomSeed]) {
+        if seeds.is_empty() {
+            return;
+        }
+        for r in seeds {
+            insert_into(rooms::table)
+                .values((
+                    rooms::id.eq(r.id),
+                    rooms::name.eq(&r.name),
+                    rooms::description.eq(&r.description),
+                ))
+                .on_conflict_do_nothing()
+                .execute(c)
+                .unwrap();
+        }
+        diesel::sql_query("SELECT setval('rooms_id_seq', (SELECT MAX(id) FROM rooms))")
+            .execute(c)
+            .unwrap();
+        RoomCache::init(rconn).clear().await;
+    }
+
+    pub async fn get_report_room(db: &Db, rconn: &RdsConn) -> QueryResult<Option<Self>> {
+        Ok(Self::all(db, rconn)
+            .await?
+            .into_iter()
+            .find(|r| r.accept_reports))
+    }
+
+    pub async fn create(db: &Db, rconn: &RdsConn, new_room: NewRoom) -> QueryResult<Self> {
+        let r = db
+            .run(move |c| {
+                insert_into(rooms::table)
+                    .values(&new_room)
+                    .get_result(with_log!(c))
+            })
+            .await?;
+        RoomCache::init(rconn).clear().await;
+        Ok(r)
+    }
+
+    pub async fn update(db: &Db, rconn: &RdsConn, id: i32, room: NewRoom) -> QueryResult<Self> {
+        let r = db
+            .run(move |c| {
+                diesel::update(rooms::table.find(id))
+                    .set(&room)
+                    .get_result(with_log!(c))
+            })
+            .await?;
+        RoomCache::init(rconn).clear().await;
+        Ok(r)
+    }
+
+    // return false if there are posts in this room
+    pub async fn delete(db: &Db, rconn: &RdsConn, id: i32) -> QueryResult<bool> {
+        let deleted = db
+            .run(move |c| {
+                let conn = with_log!(c);
+                conn.transaction::<_, diesel::result::Error, _>(|| {
+                    let n_posts: i64 = posts::table
+                        .filter(posts::room_id.eq(id))
+                        .count()
+                        .get_result(conn)?;
+                    if n_posts > 0 {
+                        return Ok(false);
+                    }
+                    diesel::delete(rooms::table.find(id)).execute(conn)?;
+                    Ok(true)
+                })
+            })
+            .await?;
+        RoomCache::init(rconn).clear().await;
+        Ok(deleted)
+    }
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PinnedPost {
+    pub post_id: i32,
+    pub room_id: Option<i32>, // None for the list of all rooms
+    pub pinned_by: String,
+    pub create_time: DateTime<Utc>,
+    pub expire_time: Option<DateTime<Utc>>,
+}
+
+impl PinnedPost {
+    fn is_active(&self) -> bool {
+        self.expire_time.map_or(true, |t| t > Utc::now())
+    }
+
+    async fn _gets_by_room(db: &Db, room_id: Option<i32>) -> QueryResult<Vec<Self>> {
+        db.run(move |c| {
+            let mut query = pinned_posts::table.into_boxed();
+            query = match room_id {
+                Some(ri) => query.filter(pinned_posts::room_id.eq(ri)),
+                None => query.filter(pinned_posts::room_id.is_null()),
+            };
+            query
+                .order(pinned_posts::create_time.desc())
+                .load(with_log!(c))
+        })
+        .await
+    }
+
+    pub async fn get_pids(db: &Db, rconn: &RdsConn, room_id: Option<i32>) -> QueryResult<Vec<i32>> {
+        let mut cacher = PinnedCache::init(room_id, rconn);
+        let pps = if let Some(pps) = cacher.get().await {
+            pps
+        } else {
+            let pps = Self::_gets_by_room(db, room_id).await?;
+            cacher.set(&pps).await;
+            pps
+        };
+        // 过期的置顶不立即删除，读取时过滤
+        Ok(pps
+            .into_iter()
+            .filter(|pp| pp.is_active())
+            .map(|pp| pp.post_id)
+            .collect())
+    }
+
+    // return false if there are already max_n pinned posts in the room
+    // 同一个洞只置顶在一处：在同一个事务中检查数量、移除原来的置顶并插入，房间已满时原来的置顶不变
+    pub async fn pin(db: &Db, rconn: &RdsConn, new_pin: Self, max_n: i64) -> QueryResult<bool> {
+        let room_id = new_pin.room_id;
+        let post_id = new_pin.post_id;
+        let old_room_ids = db
+            .run(move |c| {
+                let conn = with_log!(c);
+                conn.transaction::<_, diesel::result::Error, _>(|| {
+                    let mut query = pinned_posts::table
+                        .into_boxed()
+                        .filter(pinned_posts::post_id.ne(post_id))
+                        .filter(
+                            pinned_posts::expire_time
+                                .is_null()
+                                .or(pinned_posts::expire_time.gt(Utc::now())),
+                        );
+                    query = match room_id {
+                        Some(ri) => query.filter(pinned_posts::room_id.eq(ri)),
+                        None => query.filter(pinned_posts::room_id.is_null()),
+                    };
+                    let n: i64 = query.count().get_result(conn)?;
+                    if n >= max_n {
+                        return Ok(None);
+                    }
+                    let old_room_ids: Vec<Option<i32>> =
+                        diesel::delete(pinned_posts::table.find(post_id))
+                            .returning(pinned_posts::room_id)
+                            .get_results(conn)?;
+                    insert_into(pinned_posts::table)
+                        .values(&new_pin)
+                        .execute(conn)?;
+                    Ok(Some(old_room_ids))
+                })
+            })
+            .await?;
+        let old_room_ids = match old_room_ids {
+            Some(ids) => ids,
+            None => return Ok(false),
+        };
+        for ri in old_room_ids.into_iter().chain([room_id]) {
+            PinnedCache::init(ri, rconn).clear().await;
+        }
+        Ok(true)
+    }
+
+    pub async fn unpin(db: &Db, rconn: &RdsConn, post_id: i32) -> QueryResult<usize> {
+        let room_ids: Vec<Option<i32>> = db
+            .run(move |c| {
+                diesel::delete(pinned_posts::table.find(post_id))
+                    .returning(pinned_posts::room_id)
+                    .get_results(with_log!(c))
+            })
+            .await?;
+        for room_id in room_ids.iter() {
+            PinnedCache::init(*room_id, rconn).clear().await;
+        }
+        Ok(room_ids.len())
+    }
+}
+
 pub(crate) use {op_to_col_expr, update, with_log};
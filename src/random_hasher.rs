@@ -1,6 +1,15 @@
+use crate::rds_conn::RdsConn;
+use crate::rds_models::{get_salt, set_salt};
 use chrono::{offset::Local, DateTime};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rocket::tokio::{
+    self,
+    time::{sleep, Duration},
+};
 use sha2::{Digest, Sha256};
+use std::sync::{Arc, RwLock};
+
+const SALT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 pub fn random_string(len: usize) -> String {
     thread_rng()
@@ -11,22 +20,46 @@ pub fn random_string(len: usize) -> String {
 }
 
 pub struct RandomHasher {
-    pub salt: String,
+    salt: Arc<RwLock<String>>,
     pub start_time: DateTime<Local>,
 }
 
 impl RandomHasher {
     pub fn get_random_one() -> RandomHasher {
         RandomHasher {
-            salt: random_string(16),
+            salt: Arc::new(RwLock::new(random_string(16))),
             start_time: Local::now(),
         }
     }
 
+    // 每次启动都使用新的 salt，保存到 redis 中，命令行工具可以轮换它
+    pub async fn init(rconn: &RdsConn) -> RandomHasher {
+        let rh = Self::get_random_one();
+        let s = rh.salt.read().unwrap().clone();
+        set_salt(rconn, &s).await.unwrap();
+
+        let salt = rh.salt.clone();
+        let rconn = rconn.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(SALT_CHECK_INTERVAL).await;
+                if let Ok(Some(new_salt)) = get_salt(&rconn).await {
+                    let mut s = salt.write().unwrap();
+                    if *s != new_salt {
+                        info!("salt rotated");
+                        *s = new_salt;
+                    }
+                }
+            }
+        });
+
+        rh
+    }
+
     pub fn hash_with_salt(&self, text: &str) -> String {
         let mut h = Sha256::new();
         h.update(text);
-        h.update(&self.salt);
+        h.update(self.salt.read().unwrap().as_bytes());
         format!("{:X}", h.finalize())[5..21].to_string()
     }
 
@@ -1,41 +1,155 @@
-use redis::aio::MultiplexedConnection;
+use crate::config::{AppConfig, StoreBackend};
+use crate::mem_store::MemStore;
+use crate::metrics::REDIS_LATENCY;
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::{Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value};
 use rocket::request::{FromRequest, Outcome, Request};
-use std::env;
-use std::ops::{Deref, DerefMut};
+use rocket::tokio::time::{timeout, Duration, Instant};
+use std::io;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub struct RdsConn(pub MultiplexedConnection);
+// redis 连不上时熔断，期间所有命令直接失败，缓存退化为直接查数据库
+// 熔断到期后放行请求重试，成功则恢复
+const RDS_TIMEOUT: Duration = Duration::from_secs(2);
+const BREAKER_RETRY_MS: i64 = 10 * 1000;
+static BREAKER_OPEN_UNTIL: AtomicI64 = AtomicI64::new(0);
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+pub fn rds_available() -> bool {
+    BREAKER_OPEN_UNTIL.load(Ordering::Relaxed) <= now_ms()
+}
+
+fn check_breaker() -> RedisResult<()> {
+    if rds_available() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotConnected, "redis circuit breaker is open").into())
+    }
+}
+
+fn record_result<T>(r: &RedisResult<T>) {
+    match r {
+        Err(e) if e.kind() == ErrorKind::IoError => {
+            if rds_available() {
+                warn!("redis unavailable, open circuit breaker: {}", e);
+            }
+            BREAKER_OPEN_UNTIL.store(now_ms() + BREAKER_RETRY_MS, Ordering::Relaxed);
+        }
+        _ => {
+            if BREAKER_OPEN_UNTIL.swap(0, Ordering::Relaxed) != 0 {
+                info!("redis recovered, close circuit breaker");
+            }
+        }
+    }
+}
+
+async fn with_timeout<T>(f: RedisFuture<'_, T>) -> RedisResult<T> {
+    let start = Instant::now();
+    let r = timeout(RDS_TIMEOUT, f).await.unwrap_or_else(|_| {
+        Err(RedisError::from(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "redis command timed out",
+        )))
+    });
+    REDIS_LATENCY.observe(start.elapsed());
+    r
+}
+
+// 所有缓存和 redis 中的数据都通过 RdsConn 读写，使用 redis 的命令接口
+// 后端由配置项 store 决定，redis 或进程内存储
+#[derive(Clone)]
+enum Backend {
+    Redis(ConnectionManager),
+    Memory(Arc<MemStore>),
+}
+
+#[derive(Clone)]
+pub struct RdsConn(Backend);
+
+impl RdsConn {
+    pub async fn connect(config: &AppConfig) -> Self {
+        match config.store {
+            StoreBackend::Redis => {
+                // 配置校验保证了使用 redis 时 redis_url 存在
+                let url = config.redis_url.as_deref().unwrap();
+                RdsConn(Backend::Redis(init_rds_client(url).await))
+            }
+            StoreBackend::Memory => Self::memory(),
+        }
+    }
+
+    // 每次调用都是一个新的、独立的进程内存储
+    pub fn memory() -> Self {
+        RdsConn(Backend::Memory(Arc::new(MemStore::new())))
+    }
+}
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for RdsConn {
     type Error = ();
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let rconn = request.rocket().state::<MultiplexedConnection>().unwrap();
-        Outcome::Success(RdsConn(rconn.clone()))
+        let rconn = request.rocket().state::<RdsConn>().unwrap();
+        Outcome::Success(rconn.clone())
     }
 }
 
-impl Clone for RdsConn {
-    fn clone(&self) -> Self {
-        RdsConn(self.0.clone())
+impl ConnectionLike for RdsConn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            match &mut self.0 {
+                Backend::Redis(rmc) => {
+                    check_breaker()?;
+                    let r = with_timeout(rmc.req_packed_command(cmd)).await;
+                    record_result(&r);
+                    r
+                }
+                Backend::Memory(store) => store.query(cmd),
+            }
+        })
     }
-}
 
-impl Deref for RdsConn {
-    type Target = MultiplexedConnection;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            match &mut self.0 {
+                Backend::Redis(rmc) => {
+                    check_breaker()?;
+                    let r = with_timeout(rmc.req_packed_commands(cmd, offset, count)).await;
+                    record_result(&r);
+                    r
+                }
+                Backend::Memory(store) => {
+                    let rs = cmd
+                        .cmd_iter()
+                        .map(|c| store.query(c))
+                        .collect::<RedisResult<Vec<Value>>>()?;
+                    Ok(rs.into_iter().skip(offset).take(count).collect())
+                }
+            }
+        })
     }
-}
 
-impl DerefMut for RdsConn {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+    fn get_db(&self) -> i64 {
+        match &self.0 {
+            Backend::Redis(rmc) => rmc.get_db(),
+            Backend::Memory(_) => 0,
+        }
     }
 }
 
-pub async fn init_rds_client() -> MultiplexedConnection {
-    let redis_url = env::var("REDIS_URL").expect("REDIS_URL must be set");
+async fn init_rds_client(redis_url: &str) -> ConnectionManager {
     let client = redis::Client::open(redis_url).expect("connect to redis fail");
-    client.get_multiplexed_async_connection().await.unwrap()
+    client.get_tokio_connection_manager().await.unwrap()
 }
@@ -1,4 +1,5 @@
 use crate::api::{Api, CurrentUser, PolicyError};
+use crate::i18n::Lang;
 use crate::random_hasher::random_string;
 use crate::rds_conn::RdsConn;
 use chrono::{offset::Local, DateTime};
@@ -59,20 +60,36 @@ macro_rules! rem {
 
 macro_rules! clear_all {
     ($pattern:literal) => {
-        pub async fn clear_all(rconn: &mut RdsConn) {
-            let keys: Vec<String> = rconn
-                .scan_match::<&str, String>($pattern)
-                .await
-                .unwrap()
-                .collect::<Vec<String>>()
-                .await;
-
-            rconn
-                .del(keys)
-                .await
-                .unwrap_or_else(|e| warn!("clear all fail, pattern: {} , {}", $pattern, e));
+        pub async fn clear_all(rconn: &mut RdsConn) -> usize {
+            crate::rds_models::clear_keys(rconn, $pattern).await
         }
     };
+    ($pattern:literal, $metrics:expr) => {
+        pub async fn clear_all(rconn: &mut RdsConn) -> usize {
+            let n = crate::rds_models::clear_keys(rconn, $pattern).await;
+            $metrics.evict(n);
+            n
+        }
+    };
+}
+
+// return number of deleted keys
+pub async fn clear_keys(rconn: &mut RdsConn, pattern: &str) -> usize {
+    let keys: Vec<String> = match rconn.scan_match::<&str, String>(pattern).await {
+        Ok(iter) => iter.collect::<Vec<String>>().await,
+        Err(e) => {
+            warn!("clear all fail, pattern: {} , {}", pattern, e);
+            return 0;
+        }
+    };
+    if keys.is_empty() {
+        return 0;
+    }
+
+    rconn.del(keys).await.unwrap_or_else(|e| {
+        warn!("clear all fail, pattern: {} , {}", pattern, e);
+        0
+    })
 }
 
 const KEY_SYSTEMLOG: &str = "hole_v2:systemlog_list";
@@ -86,11 +103,14 @@ macro_rules! KEY_TITLE_SECRET {
     };
 }
 const KEY_AUTO_BLOCK_RANK: &str = "hole_v2:auto_block_rank"; // rank * 5: 自动过滤的拉黑数阈值
+const KEY_LANG: &str = "hole_v2:lang";
 const KEY_ANNOUNCEMENT: &str = "hole_v2:announcement";
+const KEY_SALT: &str = "hole_v2:salt";
 const KEY_CANDIDATE: &str = "hole_v2:candidate";
 const KEY_ADMIN: &str = "hole_v2:admin";
 
 const SYSTEMLOG_MAX_LEN: isize = 1000;
+const DRAFT_KEEP_TIME: usize = 7 * 24 * 60 * 60;
 
 pub struct Attention {
     key: String,
@@ -149,6 +169,7 @@ pub enum LogType {
     AdminDelete,
     Report,
     Ban,
+    Lock,
 }
 
 /*
@@ -336,38 +357,128 @@ impl AutoBlockRank {
     }
 }
 
-pub struct PollOption {
+pub struct LangSetting;
+
+impl LangSetting {
+    pub async fn set(rconn: &RdsConn, namehash: &str, lang: Lang) -> RedisResult<usize> {
+        rconn.clone().hset(KEY_LANG, namehash, lang.tag()).await
+    }
+
+    pub async fn get(rconn: &RdsConn, namehash: &str) -> RedisResult<Option<Lang>> {
+        let tag: Option<String> = rconn.clone().hget(KEY_LANG, namehash).await?;
+        Ok(tag.as_deref().and_then(Lang::parse))
+    }
+
+    pub async fn clear(rconn: &RdsConn, namehash: &str) -> RedisResult<usize> {
+        rconn.clone().hdel(KEY_LANG, namehash).await
+    }
+
+    pub async fn clear_all(rconn: &mut RdsConn) -> RedisResult<()> {
+        rconn.del(KEY_LANG).await
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct DraftData {
+    pub text: String,
+    pub cw: String,
+    pub update_time: DateTime<Local>,
+}
+
+// 草稿随 namehash 一起在重启时清空
+pub struct Draft {
     key: String,
     rconn: RdsConn,
 }
 
-impl PollOption {
+impl Draft {
+    // kind: "post" 或 "comment"
+    init!(&str, &str, "hole_v2:draft:{}:{}");
+
+    clear_all!("hole_v2:draft:*");
+
+    // return false if too many drafts
+    pub async fn set(&mut self, field: &str, data: &DraftData, max_n: usize) -> RedisResult<bool> {
+        if !self.rconn.hexists(&self.key, field).await?
+            && self.rconn.hlen::<&str, usize>(&self.key).await? >= max_n
+        {
+            return Ok(false);
+        }
+        self.rconn
+            .hset(&self.key, field, serde_json::to_string(data).unwrap())
+            .await?;
+        self.rconn.expire(&self.key, DRAFT_KEEP_TIME).await?;
+        Ok(true)
+    }
+
+    pub async fn get(&mut self, field: &str) -> RedisResult<Option<DraftData>> {
+        let rds_result: Option<String> = self.rconn.hget(&self.key, field).await?;
+        Ok(rds_result.and_then(|s| {
+            serde_json::from_str(&s)
+                .map_err(|e| warn!("parse draft failed: {}, {}", e, s))
+                .ok()
+        }))
+    }
+
+    pub async fn all(&mut self) -> RedisResult<Vec<(String, DraftData)>> {
+        let rds_result: Vec<(String, String)> = self.rconn.hgetall(&self.key).await?;
+        Ok(rds_result
+            .into_iter()
+            .filter_map(|(k, s)| serde_json::from_str(&s).ok().map(|d| (k, d)))
+            .collect())
+    }
+
+    pub async fn rem(&mut self, field: &str) -> RedisResult<usize> {
+        self.rconn.hdel(&self.key, field).await
+    }
+}
+
+// 投票已迁移到数据库，以下仅用于启动时导入旧数据
+pub struct LegacyPollOption {
+    key: String,
+    rconn: RdsConn,
+}
+
+impl LegacyPollOption {
     init!(i32, "hole_thu:poll_opts:{}");
 
-    pub async fn set_list(&mut self, v: &Vec<String>) -> RedisResult<()> {
-        self.rconn.del(&self.key).await?;
-        self.rconn.rpush(&self.key, v).await
+    pub async fn all_pids(rconn: &RdsConn) -> RedisResult<Vec<i32>> {
+        let mut rconn = rconn.clone();
+        let keys: Vec<String> = rconn
+            .scan_match::<&str, String>("hole_thu:poll_opts:*")
+            .await?
+            .collect::<Vec<String>>()
+            .await;
+        Ok(keys
+            .iter()
+            .filter_map(|k| k.rsplit(':').next().and_then(|pid| pid.parse().ok()))
+            .collect())
     }
 
     pub async fn get_list(&mut self) -> RedisResult<Vec<String>> {
         self.rconn.lrange(&self.key, 0, -1).await
     }
+
+    pub async fn clear(&mut self) -> RedisResult<()> {
+        self.rconn.del(&self.key).await
+    }
 }
 
-pub struct PollVote {
+pub struct LegacyPollVote {
     key: String,
     rconn: RdsConn,
 }
 
-impl PollVote {
+impl LegacyPollVote {
     init!(i32, usize, "hole_thu:poll_votes:{}:{}");
 
-    add!(&str);
-
-    has!(&str);
+    pub async fn all(&mut self) -> RedisResult<Vec<String>> {
+        self.rconn.smembers(&self.key).await
+    }
 
-    pub async fn count(&mut self) -> RedisResult<usize> {
-        self.rconn.scard(&self.key).await
+    pub async fn clear(&mut self) -> RedisResult<()> {
+        self.rconn.del(&self.key).await
     }
 }
 
@@ -375,14 +486,33 @@ pub async fn clear_outdate_redis_data(rconn: &mut RdsConn) {
     BannedUsers::clear(rconn).await.unwrap();
     CustomTitle::clear(rconn).await.unwrap();
     AutoBlockRank::clear(rconn).await.unwrap();
+    LangSetting::clear_all(rconn).await.unwrap();
     Attention::clear_all(rconn).await;
     BlockedUsers::clear_all(rconn).await;
+    Draft::clear_all(rconn).await;
 }
 
 pub async fn get_announcement(rconn: &RdsConn) -> RedisResult<Option<String>> {
     rconn.clone().get(KEY_ANNOUNCEMENT).await
 }
 
+// 为空时清除公告
+pub async fn set_announcement(rconn: &RdsConn, text: &str) -> RedisResult<()> {
+    if text.is_empty() {
+        rconn.clone().del(KEY_ANNOUNCEMENT).await
+    } else {
+        rconn.clone().set(KEY_ANNOUNCEMENT, text).await
+    }
+}
+
+pub async fn get_salt(rconn: &RdsConn) -> RedisResult<Option<String>> {
+    rconn.clone().get(KEY_SALT).await
+}
+
+pub async fn set_salt(rconn: &RdsConn, salt: &str) -> RedisResult<()> {
+    rconn.clone().set(KEY_SALT, salt).await
+}
+
 pub async fn is_elected_candidate(rconn: &RdsConn, title: &Option<String>) -> RedisResult<bool> {
     if let Some(t) = title {
         rconn.clone().sismember(KEY_CANDIDATE, t).await
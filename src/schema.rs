@@ -12,6 +12,42 @@ table! {
     }
 }
 
+table! {
+    pinned_posts (post_id) {
+        post_id -> Int4,
+        room_id -> Nullable<Int4>,
+        pinned_by -> Varchar,
+        create_time -> Timestamptz,
+        expire_time -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    poll_options (id) {
+        id -> Int4,
+        poll_id -> Int4,
+        idx -> Int4,
+        content -> Varchar,
+    }
+}
+
+table! {
+    poll_votes (poll_id, voter_hash) {
+        poll_id -> Int4,
+        voter_hash -> Varchar,
+        option_id -> Int4,
+        create_time -> Timestamptz,
+    }
+}
+
+table! {
+    polls (id) {
+        id -> Int4,
+        post_id -> Int4,
+        create_time -> Timestamptz,
+    }
+}
+
 table! {
     posts (id) {
         id -> Int4,
@@ -31,6 +67,32 @@ table! {
         room_id -> Int4,
         up_votes -> Int4,
         down_votes -> Int4,
+        is_locked -> Bool,
+        expire_time -> Nullable<Timestamptz>,
+        is_scheduled -> Bool,
+    }
+}
+
+table! {
+    reactions (post_id, comment_id, user_hash, emoji) {
+        post_id -> Int4,
+        comment_id -> Int4,
+        user_hash -> Varchar,
+        emoji -> Varchar,
+        create_time -> Timestamptz,
+    }
+}
+
+table! {
+    rooms (id) {
+        id -> Int4,
+        name -> Varchar,
+        description -> Varchar,
+        is_visible -> Bool,
+        admin_only -> Bool,
+        allow_delete -> Bool,
+        allow_tmp -> Bool,
+        accept_reports -> Bool,
     }
 }
 
@@ -44,9 +106,21 @@ table! {
 }
 
 joinable!(comments -> posts (post_id));
+joinable!(pinned_posts -> posts (post_id));
+joinable!(poll_options -> polls (poll_id));
+joinable!(poll_votes -> poll_options (option_id));
+joinable!(poll_votes -> polls (poll_id));
+joinable!(polls -> posts (post_id));
+joinable!(reactions -> posts (post_id));
 
 allow_tables_to_appear_in_same_query!(
     comments,
+    pinned_posts,
+    poll_options,
+    poll_votes,
+    polls,
     posts,
+    reactions,
+    rooms,
     users,
 );
@@ -0,0 +1,292 @@
+// 各缓存在进程内存储上的读写和清除，不需要 postgres
+use crate::api::CurrentUser;
+use crate::cache::*;
+use crate::i18n::Lang;
+use crate::models::{Comment, EmojiReaction, PinnedPost, PollData, PollOption, Post, Room, User};
+use crate::rds_conn::RdsConn;
+use crate::rds_models::BlockCounter;
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+
+// 发帖时间随 pid 递增，与按时间排序的列表一致
+fn post(id: i32) -> Post {
+    let create_time = Utc::now() + Duration::seconds(id.into());
+    Post {
+        id,
+        author_hash: "author".to_string(),
+        content: format!("#{}", id),
+        cw: String::new(),
+        author_title: String::new(),
+        is_tmp: false,
+        n_attentions: 0,
+        n_comments: 0,
+        create_time,
+        last_comment_time: create_time,
+        is_deleted: false,
+        is_reported: false,
+        hot_score: 0,
+        allow_search: true,
+        room_id: 0,
+        up_votes: 0,
+        down_votes: 0,
+        is_locked: false,
+        expire_time: None,
+        is_scheduled: false,
+    }
+}
+
+fn comment(id: i32, post_id: i32) -> Comment {
+    Comment {
+        id,
+        author_hash: "author".to_string(),
+        author_title: String::new(),
+        is_tmp: false,
+        content: format!("re #{}", post_id),
+        create_time: Utc::now(),
+        is_deleted: false,
+        allow_search: true,
+        post_id,
+    }
+}
+
+#[rocket::async_test]
+async fn post_cache() {
+    let mut rconn = RdsConn::memory();
+    let (p1, p2) = (post(1), post(2));
+    let mut c = PostCache::init(&rconn);
+    c.sets(&[&p1, &p2]).await;
+
+    assert_eq!(c.get(&1).await.unwrap().content, "#1");
+    let ps = c.gets(&[2, 3, 1]).await;
+    assert_eq!(
+        ps.iter()
+            .map(|p| p.as_ref().map(|p| p.id))
+            .collect::<Vec<_>>(),
+        [Some(2), None, Some(1)]
+    );
+
+    assert_eq!(PostCache::clear_all(&mut rconn).await, 2);
+    assert!(c.get(&1).await.is_none());
+}
+
+#[rocket::async_test]
+async fn post_comment_cache() {
+    let mut rconn = RdsConn::memory();
+    let mut c = PostCommentCache::init(1, &rconn);
+    assert!(c.get().await.is_none());
+    c.set(&[comment(1, 1), comment(2, 1)]).await;
+    let cs = c.get().await.unwrap();
+    assert_eq!(cs.iter().map(|c| c.id).collect::<Vec<_>>(), [1, 2]);
+
+    c.clear().await;
+    assert!(c.get().await.is_none());
+
+    c.set(&[]).await;
+    PostCommentCache::init(2, &rconn)
+        .set(&[comment(3, 2)])
+        .await;
+    assert_eq!(PostCommentCache::clear_all(&mut rconn).await, 2);
+}
+
+#[rocket::async_test]
+async fn poll_cache() {
+    let mut rconn = RdsConn::memory();
+    // 没有投票也缓存，与未缓存区分
+    let mut c = PollCache::init(1, &rconn);
+    assert!(c.get().await.is_none());
+    c.set(&None).await;
+    assert!(matches!(c.get().await, Some(None)));
+
+    let mut c = PollCache::init(2, &rconn);
+    c.set(&Some(PollData {
+        post_id: 2,
+        poll_id: 1,
+        options: vec![PollOption {
+            id: 1,
+            poll_id: 1,
+            idx: 0,
+            content: "好".to_string(),
+        }],
+        votes: vec![1],
+        voters: HashMap::from([("voter".to_string(), 0)]),
+    }))
+    .await;
+    let pd = c.get().await.unwrap().unwrap();
+    assert_eq!(pd.votes, [1]);
+    assert_eq!(pd.voters["voter"], 0);
+
+    c.clear().await;
+    assert!(c.get().await.is_none());
+    assert_eq!(PollCache::clear_all(&mut rconn).await, 1);
+}
+
+#[rocket::async_test]
+async fn emoji_reaction_cache() {
+    let mut rconn = RdsConn::memory();
+    let mut c = EmojiReactionCache::init(1, &rconn);
+    assert!(c.get().await.is_none());
+    c.set(&[EmojiReaction {
+        comment_id: 0,
+        user_hash: "someone".to_string(),
+        emoji: "👍".to_string(),
+    }])
+    .await;
+    assert_eq!(c.get().await.unwrap()[0].emoji, "👍");
+
+    c.clear().await;
+    assert!(c.get().await.is_none());
+    c.set(&[]).await;
+    assert_eq!(EmojiReactionCache::clear_all(&mut rconn).await, 1);
+}
+
+#[rocket::async_test]
+async fn post_list_cache() {
+    let mut rconn = RdsConn::memory();
+    let mut c = PostListCache::init(None, 0, &rconn);
+    assert!(c.need_fill().await);
+    assert_eq!(c.get_pids(0, 1).await, None);
+
+    let ps: Vec<Post> = (1..=5).map(post).collect();
+    c.fill(&ps).await;
+    assert_eq!(c.i64_len(), 5);
+    assert_eq!(c.get_pids(0, 3).await, Some(vec![5, 4, 3]));
+    assert_eq!(c.get_pids(3, 3).await, None);
+
+    let page = c.get_after(None, 2).await.unwrap();
+    assert_eq!(
+        page,
+        [
+            (ps[4].list_key(0, None).unwrap(), 5),
+            (ps[3].list_key(0, None).unwrap(), 4)
+        ]
+    );
+    let page = c.get_after(page.last().copied(), 2).await.unwrap();
+    assert_eq!(page.iter().map(|&(_, pid)| pid).collect::<Vec<_>>(), [3, 2]);
+    // 剩下的不够一页，交给数据库
+    assert_eq!(c.get_after(page.last().copied(), 2).await, None);
+
+    let mut deleted = post(4);
+    deleted.is_deleted = true;
+    c.put(&deleted).await;
+    c.put(&post(6)).await;
+    c.need_fill().await;
+    assert_eq!(c.get_pids(0, 5).await, Some(vec![6, 5, 3, 2, 1]));
+
+    c.clear().await;
+    assert!(c.need_fill().await);
+    assert_eq!(c.i64_len(), 0);
+
+    c.fill(&ps).await;
+    assert_eq!(PostListCache::clear_all(&mut rconn).await, 1);
+}
+
+#[rocket::async_test]
+async fn post_list_cache_ties() {
+    let rconn = RdsConn::memory();
+    // 排序值相同时按 pid 从大到小，与数据库一致，而不是 redis 的字典序
+    let mut c = PostListCache::init(Some(1), 2, &rconn);
+    let ps: Vec<Post> = [8, 9, 10, 11]
+        .into_iter()
+        .map(|id| {
+            let mut p = post(id);
+            p.hot_score = i32::from(id == 8) * 5;
+            p
+        })
+        .collect();
+    c.fill(&ps).await;
+
+    let page = c.get_after(None, 2).await.unwrap();
+    assert_eq!(page, [(-5, 8), (0, 11)]);
+    let page = c.get_after(page.last().copied(), 2).await.unwrap();
+    assert_eq!(page, [(0, 10), (0, 9)]);
+    assert_eq!(c.get_after(Some((0, 10)), 1).await, Some(vec![(0, 9)]));
+}
+
+#[rocket::async_test]
+async fn pinned_cache() {
+    let mut rconn = RdsConn::memory();
+    let mut c = PinnedCache::init(Some(1), &rconn);
+    assert!(c.get().await.is_none());
+    c.set(&[PinnedPost {
+        post_id: 1,
+        room_id: Some(1),
+        pinned_by: "admin".to_string(),
+        create_time: Utc::now(),
+        expire_time: None,
+    }])
+    .await;
+    assert_eq!(c.get().await.unwrap()[0].post_id, 1);
+    // 所有房间的列表是单独的一项
+    assert!(PinnedCache::init(None, &rconn).get().await.is_none());
+
+    c.clear().await;
+    assert!(c.get().await.is_none());
+    c.set(&[]).await;
+    assert_eq!(PinnedCache::clear_all(&mut rconn).await, 1);
+}
+
+#[rocket::async_test]
+async fn user_cache() {
+    let mut rconn = RdsConn::memory();
+    let mut c = UserCache::init("token", &rconn);
+    assert!(c.get().await.is_none());
+    c.set(&User {
+        id: 1,
+        name: "alice".to_string(),
+        token: "token".to_string(),
+        is_admin: false,
+    })
+    .await;
+    assert_eq!(c.get().await.unwrap().name, "alice");
+    assert!(UserCache::init("other", &rconn).get().await.is_none());
+
+    assert_eq!(UserCache::clear_all(&mut rconn).await, 1);
+    assert!(c.get().await.is_none());
+}
+
+#[rocket::async_test]
+async fn block_dict_cache() {
+    let mut rconn = RdsConn::memory();
+    let user = CurrentUser::from_hash(&rconn, "me".to_string(), Lang::default()).await;
+    let (a, b) = ("a".to_string(), "b".to_string());
+    let mut c = BlockDictCache::init("me", 1, &rconn);
+
+    let dict = c.get_or_create(&user, &[&a, &b]).await.unwrap();
+    assert_eq!(dict.get("a"), Some(&false));
+    assert_eq!(dict.get("b"), Some(&false));
+
+    // 被拉黑次数达到阈值后，清除缓存才会重新计算
+    for _ in 0..10 {
+        BlockCounter::count_incr(&rconn, &a).await.unwrap();
+    }
+    let dict = c.get_or_create(&user, &[&a]).await.unwrap();
+    assert_eq!(dict.get("a"), Some(&false));
+    c.clear().await.unwrap();
+    let dict = c.get_or_create(&user, &[&a, &b]).await.unwrap();
+    assert_eq!(dict.get("a"), Some(&true));
+    assert_eq!(dict.get("b"), Some(&false));
+
+    assert_eq!(BlockDictCache::clear_all(&mut rconn).await, 1);
+}
+
+#[rocket::async_test]
+async fn room_cache() {
+    let rconn = RdsConn::memory();
+    let mut c = RoomCache::init(&rconn);
+    assert!(c.get().await.is_none());
+    c.set(&[Room {
+        id: 1,
+        name: "树洞".to_string(),
+        description: String::new(),
+        is_visible: true,
+        admin_only: false,
+        allow_delete: true,
+        allow_tmp: true,
+        accept_reports: false,
+    }])
+    .await;
+    assert_eq!(c.get().await.unwrap()[0].name, "树洞");
+
+    c.clear().await;
+    assert!(c.get().await.is_none());
+}
@@ -0,0 +1,458 @@
+use super::TestApp;
+use crate::db_conn::JobConn;
+use crate::models::{ListCursor, Post};
+use crate::schema::posts;
+use chrono::Utc;
+use diesel::prelude::*;
+use rocket::tokio::time::{sleep, Duration};
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn requires_login() {
+    let app = TestApp::start().await;
+    let r = app
+        .get("invalid-token", "/_api/v1/getlist?order_mode=0")
+        .await;
+    assert_eq!(r.status, 401);
+    assert_eq!(r.err(), "unauthorized");
+
+    let r = app.get("invalid-token", "/_api/v1/no_such_api").await;
+    assert_eq!(r.status, 404);
+    assert_eq!(r.err(), "unknown_api");
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn publish_comment_attention_reaction() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+
+    let pid = app
+        .publish(
+            &alice,
+            &[("text", "第一条"), ("cw", ""), ("allow_search", "1")],
+        )
+        .await;
+    let list = app.get(&bob, "/_api/v1/getlist?order_mode=0").await;
+    assert_eq!(list.ok()[0]["pid"], pid);
+    assert_eq!(list.ok()[0]["text"], "第一条");
+
+    let p = app
+        .get(&alice, &format!("/_api/v1/getone?pid={}", pid))
+        .await;
+    assert_eq!(p.ok()["n_comments"], 0);
+    assert_eq!(p.ok()["n_attentions"], 1);
+    assert_eq!(p.ok()["attention"], true);
+    assert_eq!(p.ok()["can_del"], true);
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    // 评论
+    app.post(
+        &bob,
+        &format!("/_api/v2/post/{}/comment", pid),
+        &[("text", "沙发")],
+    )
+    .await
+    .ok();
+    let cs = app
+        .get(&alice, &format!("/_api/v1/getcomment?pid={}", pid))
+        .await;
+    assert_eq!(cs.ok().as_array().unwrap().len(), 1);
+    assert_eq!(cs.ok()[0]["text"], "沙发");
+    assert_eq!(cs.ok()[0]["name_id"], 1);
+    let p = app.get(&bob, &format!("/_api/v1/getone?pid={}", pid)).await;
+    assert_eq!(p.ok()["n_comments"], 1);
+    assert_eq!(p.ok()["reply"], 1);
+    assert_eq!(app.db_post(pid).n_comments, 1);
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    // 关注，重复关注不改变计数
+    for _ in 0..2 {
+        let r = app
+            .post(
+                &bob,
+                "/_api/v1/attention",
+                &[("pid", &pid.to_string()), ("switch", "1")],
+            )
+            .await;
+        assert_eq!(r.code(), 0);
+        assert_eq!(r.body["n_attentions"], 2);
+        assert_eq!(r.body["likenum"], 2);
+    }
+    let att = app.get(&bob, "/_api/v1/getattention").await;
+    assert_eq!(att.ok()[0]["pid"], pid);
+    assert_eq!(app.db_post(pid).n_attentions, 2);
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    let r = app
+        .post(
+            &bob,
+            "/_api/v1/attention",
+            &[("pid", &pid.to_string()), ("switch", "0")],
+        )
+        .await;
+    assert_eq!(r.body["n_attentions"], 1);
+    let att = app.get(&bob, "/_api/v1/getattention").await;
+    assert!(att.ok().as_array().unwrap().is_empty());
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    // 赞和踩
+    let uri = format!("/_api/v2/post/{}/reaction", pid);
+    let r = app.post(&bob, &uri, &[("status", "1")]).await;
+    assert_eq!(r.ok()["up_votes"], 1);
+    assert_eq!(r.ok()["down_votes"], 0);
+    let r = app.post(&bob, &uri, &[("status", "-1")]).await;
+    assert_eq!(r.ok()["up_votes"], 0);
+    assert_eq!(r.ok()["down_votes"], 1);
+    let p = app.get(&bob, &format!("/_api/v1/getone?pid={}", pid)).await;
+    assert_eq!(p.ok()["reaction_status"], -1);
+    assert_eq!(p.ok()["down_votes"], 1);
+    let p = app.db_post(pid);
+    assert_eq!((p.up_votes, p.down_votes), (0, 1));
+    app.assert_post_cache_consistent(&alice, pid).await;
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn vote() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+
+    let pid = app
+        .publish(
+            &alice,
+            &[
+                ("text", "投票"),
+                ("cw", ""),
+                ("allow_search", "1"),
+                ("poll_options", "甲"),
+                ("poll_options", "乙"),
+            ],
+        )
+        .await;
+    let pid = pid.to_string();
+
+    let r = app
+        .post(&bob, "/_api/v1/vote", &[("pid", &pid), ("vote", "乙")])
+        .await;
+    assert_eq!(r.ok()["vote"], "乙");
+    assert_eq!(r.ok()["answers"][0]["votes"], 0);
+    assert_eq!(r.ok()["answers"][1]["votes"], 1);
+
+    // 不能重复投票，也不能投不存在的选项
+    let r = app
+        .post(&bob, "/_api/v1/vote", &[("pid", &pid), ("vote", "甲")])
+        .await;
+    assert_eq!(r.status, 403);
+    assert_eq!(r.err(), "not_allowed");
+    let r = app
+        .post(&alice, "/_api/v1/vote", &[("pid", &pid), ("vote", "丙")])
+        .await;
+    assert_eq!(r.code(), -1);
+
+    // 只能导出为 csv 或 json
+    let r = app
+        .get(
+            &alice,
+            &format!("/_api/v2/post/{}/poll/export?format=xml", pid),
+        )
+        .await;
+    assert_eq!(r.status, 422);
+    assert_eq!(r.err(), "invalid_format");
+
+    let p = app
+        .get(&alice, &format!("/_api/v1/getone?pid={}", pid))
+        .await;
+    assert_eq!(p.ok()["poll"]["answers"][1]["votes"], 1);
+    assert!(p.ok()["poll"]["vote"].is_null());
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn scheduled_post_on_top() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+
+    let publish_at = (Utc::now().timestamp() + 60).to_string();
+    let pid = app
+        .publish(
+            &alice,
+            &[
+                ("text", "定时"),
+                ("cw", ""),
+                ("allow_search", "1"),
+                ("publish_at", &publish_at),
+            ],
+        )
+        .await;
+    for text in ["第一条", "第二条"] {
+        app.publish(&bob, &[("text", text), ("cw", ""), ("allow_search", "1")])
+            .await;
+    }
+    let list = app.get(&bob, "/_api/v1/getlist?order_mode=0").await;
+    assert_eq!(list.ok().as_array().unwrap().len(), 2);
+
+    // 发布时间提前到现在，pid 比之后发的洞小，仍应排在最前面
+    sleep(Duration::from_secs(1)).await;
+    diesel::update(posts::table.find(pid))
+        .set(posts::create_time.eq(Utc::now()))
+        .execute(&app.conn())
+        .unwrap();
+    Post::publish_scheduled(&mut JobConn::new(&app.db.url), &app.rconn)
+        .await
+        .unwrap();
+
+    let list = app.get(&bob, "/_api/v1/getlist?order_mode=0").await;
+    assert_eq!(list.ok()[0]["pid"], pid);
+    assert_eq!(list.ok().as_array().unwrap().len(), 3);
+    app.assert_post_cache_consistent(&alice, pid).await;
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn report_and_delete() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+    let admin = app.create_user("admin", true);
+
+    let pid = app
+        .publish(
+            &alice,
+            &[("text", "被举报"), ("cw", ""), ("allow_search", "1")],
+        )
+        .await;
+    let r = app
+        .post(
+            &bob,
+            "/_api/v1/report",
+            &[
+                ("pid", &pid.to_string()),
+                ("reason", "测试"),
+                ("should_hide", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(r.code(), 0);
+    assert!(app.db_post(pid).is_reported);
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    // 举报被自动发到举报房间
+    let reports = app.db_posts_in_room(42);
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].content.starts_with("[系统自动代发]"));
+    assert!(reports[0].content.contains(&format!("#{}", pid)));
+
+    // 被举报后只有管理员可见
+    let r = app.get(&bob, &format!("/_api/v1/getone?pid={}", pid)).await;
+    assert_eq!(r.err(), "is_reported");
+    let r = app
+        .get(&admin, &format!("/_api/v1/getone?pid={}", pid))
+        .await;
+    assert_eq!(r.ok()["is_reported"], true);
+
+    // 管理员关注后取消隐藏
+    app.post(
+        &admin,
+        "/_api/v1/attention",
+        &[("pid", &pid.to_string()), ("switch", "1")],
+    )
+    .await
+    .ok();
+    assert!(!app.db_post(pid).is_reported);
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    // 其他人不能删除
+    let r = app
+        .post(
+            &bob,
+            "/_api/v1/delete",
+            &[("type", "pid"), ("id", &pid.to_string()), ("note", "")],
+        )
+        .await;
+    assert_eq!(r.code(), -1);
+    assert!(!app.db_post(pid).is_deleted);
+
+    app.post(
+        &alice,
+        "/_api/v1/delete",
+        &[("type", "pid"), ("id", &pid.to_string()), ("note", "")],
+    )
+    .await
+    .ok();
+    assert!(app.db_post(pid).is_deleted);
+    app.assert_post_cache_consistent(&alice, pid).await;
+    let r = app.get(&bob, &format!("/_api/v1/getone?pid={}", pid)).await;
+    assert_eq!(r.status, 404);
+    assert_eq!(r.err(), "is_deleted");
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn delete_with_comments() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+
+    let pid = app
+        .publish(
+            &alice,
+            &[("text", "有评论"), ("cw", ""), ("allow_search", "1")],
+        )
+        .await;
+    app.post(
+        &bob,
+        &format!("/_api/v2/post/{}/comment", pid),
+        &[("text", "评论")],
+    )
+    .await
+    .ok();
+
+    // 有评论时洞主删除只清空主楼
+    app.post(
+        &alice,
+        "/_api/v1/delete",
+        &[("type", "pid"), ("id", &pid.to_string()), ("note", "")],
+    )
+    .await
+    .ok();
+    let p = app.db_post(pid);
+    assert!(!p.is_deleted);
+    assert_eq!(p.content, "[洞主已删除]");
+    app.assert_post_cache_consistent(&alice, pid).await;
+
+    let cs = app
+        .get(&bob, &format!("/_api/v1/getcomment?pid={}", pid))
+        .await;
+    let cid = cs.ok()[0]["cid"].as_i64().unwrap().to_string();
+    app.post(
+        &bob,
+        "/_api/v1/delete",
+        &[("type", "cid"), ("id", &cid), ("note", "")],
+    )
+    .await
+    .ok();
+    assert_eq!(app.db_post(pid).n_comments, 0);
+    app.assert_post_cache_consistent(&alice, pid).await;
+    let cs = app
+        .get(&bob, &format!("/_api/v1/getcomment?pid={}", pid))
+        .await;
+    assert!(cs.ok().as_array().unwrap().is_empty());
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn localised_messages() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+
+    let pid = app
+        .publish(
+            &alice,
+            &[("text", "多语言"), ("cw", ""), ("allow_search", "1")],
+        )
+        .await;
+    let pid = pid.to_string();
+    let delete = [("type", "pid"), ("id", pid.as_str()), ("note", "")];
+
+    let r = app.post(&bob, "/_api/v1/delete", &delete).await;
+    assert_eq!(r.err(), "not_allowed");
+    assert_eq!(r.body["msg"], "不允许的操作");
+
+    let r = app.post(&bob, "/_api/v1/lang", &[("lang", "en-US")]).await;
+    assert_eq!(r.ok(), "en");
+    let r = app.post(&bob, "/_api/v1/delete", &delete).await;
+    assert_eq!(r.err(), "not_allowed");
+    assert_eq!(r.body["msg"], "Operation not allowed");
+
+    let r = app.post(&bob, "/_api/v1/lang", &[("lang", "fr")]).await;
+    assert_eq!(r.err(), "unknown_lang");
+
+    // 清除设置后恢复默认
+    app.post(&bob, "/_api/v1/lang", &[("lang", "")]).await.ok();
+    let r = app.post(&bob, "/_api/v1/delete", &delete).await;
+    assert_eq!(r.body["msg"], "不允许的操作");
+}
+
+#[rocket::async_test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn cursor_pagination() {
+    let app = TestApp::start().await;
+    let alice = app.create_user("alice", false);
+    let bob = app.create_user("bob", false);
+
+    for i in 0..30 {
+        let text = format!("第{}条", i);
+        app.publish(
+            &alice,
+            &[("text", &text), ("cw", ""), ("allow_search", "1")],
+        )
+        .await;
+    }
+    let pids = |r: &super::Res| -> Vec<i64> {
+        r.ok()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["pid"].as_i64().unwrap())
+            .collect()
+    };
+
+    let first = app.get(&bob, "/_api/v1/getlist?order_mode=0").await;
+    let first_pids = pids(&first);
+    assert_eq!(first_pids.len(), 25);
+    let cursor = first.body["next_cursor"].as_str().unwrap().to_string();
+
+    // 翻页前发的新洞不会让下一页重复
+    app.publish(
+        &alice,
+        &[("text", "新的"), ("cw", ""), ("allow_search", "1")],
+    )
+    .await;
+    let second = app
+        .get(
+            &bob,
+            &format!("/_api/v1/getlist?order_mode=0&cursor={}", cursor),
+        )
+        .await;
+    let second_pids = pids(&second);
+    assert_eq!(second_pids.len(), 5);
+    assert!(second_pids.iter().all(|pid| !first_pids.contains(pid)));
+    assert!(second_pids[0] < *first_pids.last().unwrap());
+    assert!(second.body["next_cursor"].is_null());
+
+    // 旧版前端的页码仍然可用
+    let page2 = app.get(&bob, "/_api/v1/getlist?order_mode=0&p=2").await;
+    assert_eq!(pids(&page2).len(), 6);
+
+    // 排序方式不同的游标无效
+    let r = app
+        .get(
+            &bob,
+            &format!("/_api/v1/getlist?order_mode=2&cursor={}", cursor),
+        )
+        .await;
+    assert_eq!(r.err(), "invalid_cursor");
+
+    // 热度参数改变前生成的游标无效
+    let hot = app.get(&bob, "/_api/v1/getlist?order_mode=5").await;
+    let hot_cursor = hot.body["next_cursor"].as_str().unwrap();
+    assert!(ListCursor::decode(hot_cursor, 5).is_some());
+    let start = ListCursor::start(5);
+    let stale = ListCursor {
+        decay: start.decay.wrapping_add(1),
+        last: Some((0, 1)),
+        ..start
+    };
+    let r = app
+        .get(
+            &bob,
+            &format!("/_api/v1/getlist?order_mode=5&cursor={}", stale.encode()),
+        )
+        .await;
+    assert_eq!(r.err(), "invalid_cursor");
+}